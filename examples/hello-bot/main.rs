@@ -1,20 +1,30 @@
-use hiven_rs::{Client, EventHandler, client::Error as ClientError, data::{House, Message}, gateway::EventInitState};
+use hiven_rs::{Client, EventHandler, GatewayExit, client::Error as ClientError, data::{House, Message}, gateway::EventInitState};
 use std::{future::Future, pin::Pin};
 use tokio::time::delay_for;
 
 #[tokio::main]
 async fn main() -> Result<(), ClientError> {
-	let client = Client::new("token");
-	client.start_gateway(MyEventHandler).await
+	// Reads the token from HIVEN_TOKEN.
+	let client = Client::from_env()?;
+	match client.start_gateway_until_ctrl_c(MyEventHandler).await {
+		GatewayExit::AuthFailed => {
+			println!("Hiven didn't accept my token.");
+			Ok(())
+		},
+		GatewayExit::Fatal(err) => Err(err),
+		_ => Ok(())
+	}
 }
 
 struct MyEventHandler;
 
 impl EventHandler for MyEventHandler {
 	fn on_connect<'c>(&self, _client: &'c Client, event: EventInitState) ->
-			Pin<Box<dyn Future<Output = ()> + 'c>> {
+			Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
 		let output = async move {
 			println!("I am @{}, also known as {}.", event.user.username, event.user.name);
+			println!("I'm in {} houses and {} private rooms.", event.house_ids.len(),
+				event.private_rooms.len());
 			//println!("{:?}", event);
 		};
 
@@ -22,7 +32,7 @@ impl EventHandler for MyEventHandler {
 	}
 
 	fn on_house_join<'c>(&self, _client: &'c Client, event: House) ->
-			Pin<Box<dyn Future<Output = ()> + 'c>> {
+			Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
 		let output = async move {
 			println!("I just joined a house named {}.", event.name);
 			//println!("{:?}", event);
@@ -32,20 +42,23 @@ impl EventHandler for MyEventHandler {
 	}
 
 	fn on_message<'c>(&self, client: &'c Client, event: Message) ->
-			Pin<Box<dyn Future<Output = ()> + 'c>> {
+			Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
 		let output = async move {
 			println!("I just heard someone say {}.", event.content);
 
-			if event.content.starts_with("$") {match &event.content[1..] {
-				"hello" => {
-					println!("I'm going to say hello back!");
+			if let Some("hello") = event.content.strip_prefix('$') {
+				println!("I'm going to say hello back!");
 
-					client.trigger_typing(event.room_id).await;
+				let reply = async {
+					client.trigger_typing(event.room_id).await?;
 					delay_for(std::time::Duration::from_millis(1000)).await;
-					client.send_message(event.room_id, "Hello!".to_owned()).await
-				},
-				_ => ()
-			}}
+					client.send_message(event.room_id, "Hello!").await
+				};
+
+				if let Err(err) = reply.await {
+					println!("I couldn't say hello back: {:?}", err);
+				}
+			}
 		};
 
 		Box::pin(output)
@@ -0,0 +1,222 @@
+//! A cache of the houses the client is in, kept up to date by the gateway.
+//!
+//! Only available with the `cache` feature. The cache is filled from the
+//! initial state and `HOUSE_JOIN` events, and houses are evicted, along with
+//! their rooms and members, when the client leaves them or they're deleted.
+//! Lookups return clones, so the cache is never locked for longer than a
+//! lookup takes.
+//!
+//! The cache also remembers responses to api requests that get polled, such
+//! as [`Client::get_house`], so hiven can answer that nothing changed instead
+//! of sending the same data again. Only the last 256 urls are remembered.
+//!
+//! [`Client::get_house`]: crate::client::Client::get_house
+
+use self::super::{data::{House, HouseId, Room, RoomId}, gateway::OpCodeEvent};
+use std::{collections::{HashMap, VecDeque}, sync::RwLock};
+
+// How many api urls to remember responses for.
+const RESPONSE_CAPACITY: usize = 256;
+
+#[derive(Debug, Default)]
+pub struct Cache {
+	houses: RwLock<HashMap<HouseId, House>>,
+	// Maps room ids to the id of the house they're in.
+	rooms: RwLock<HashMap<RoomId, HouseId>>,
+	// The ETag and body of the last response from polled api urls.
+	responses: RwLock<Responses>
+}
+
+#[derive(Debug, Default)]
+struct Responses {
+	bodies: HashMap<String, (String, Vec<u8>)>,
+	// Urls in the order they were first cached, oldest first.
+	urls: VecDeque<String>
+}
+
+impl Cache {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Gets a house by its id.
+	pub fn house<H>(&self, house: H) -> Option<House>
+			where H: Into<HouseId> {
+		self.houses.read().unwrap().get(&house.into()).cloned()
+	}
+
+	/// Gets the ids of every cached house.
+	pub fn house_ids(&self) -> Vec<HouseId> {
+		self.houses.read().unwrap().keys().copied().collect()
+	}
+
+	/// Gets a room within any cached house by its id.
+	pub fn room<R>(&self, room: R) -> Option<Room>
+			where R: Into<RoomId> {
+		let room_id = room.into();
+		let house_id = *self.rooms.read().unwrap().get(&room_id)?;
+		self.houses.read().unwrap().get(&house_id)?.rooms.iter()
+			.find(|room| room.id == room_id).cloned()
+	}
+
+	/// Gets the id of the house a room is in.
+	pub fn room_house_id<R>(&self, room: R) -> Option<HouseId>
+			where R: Into<RoomId> {
+		self.rooms.read().unwrap().get(&room.into()).copied()
+	}
+
+	/// Adds a house, replacing it if it's already cached.
+	pub fn insert_house(&self, house: House) {
+		// Evict first, so rooms the house no longer has don't linger.
+		self.remove_house(house.id);
+
+		// Always lock houses before rooms.
+		let mut houses = self.houses.write().unwrap();
+		let mut rooms = self.rooms.write().unwrap();
+		for room in &house.rooms {rooms.insert(room.id, house.id);}
+		houses.insert(house.id, house);
+	}
+
+	/// Evicts a house, along with its rooms and members. Returns the house if
+	/// it was cached.
+	pub fn remove_house<H>(&self, house: H) -> Option<House>
+			where H: Into<HouseId> {
+		let house = self.houses.write().unwrap().remove(&house.into())?;
+
+		let mut rooms = self.rooms.write().unwrap();
+		for room in &house.rooms {rooms.remove(&room.id);}
+		Some(house)
+	}
+
+	/// Gets the ETag and body of the last response from an api url, if hiven
+	/// sent an ETag with it.
+	pub(crate) fn response(&self, url: &str) -> Option<(String, Vec<u8>)> {
+		self.responses.read().unwrap().bodies.get(url).cloned()
+	}
+
+	/// Remembers a response, forgetting the oldest url once there are too many.
+	pub(crate) fn insert_response(&self, url: String, etag: String, body: Vec<u8>) {
+		let mut responses = self.responses.write().unwrap();
+		if responses.bodies.insert(url.clone(), (etag, body)).is_some() {return}
+
+		responses.urls.push_back(url);
+		if responses.urls.len() > RESPONSE_CAPACITY {
+			if let Some(url) = responses.urls.pop_front() {responses.bodies.remove(&url);}
+		}
+	}
+
+	/// Updates the cache from an event, before it's passed to the event
+	/// handler.
+	pub(crate) fn update(&self, event: &OpCodeEvent) {
+		match event {
+			OpCodeEvent::InitState(data) =>
+				for house in &data.houses {self.insert_house(house.clone())},
+			OpCodeEvent::HouseJoin(house) => self.insert_house(house.clone()),
+			OpCodeEvent::HouseLeave(data) => {self.remove_house(data.house_id);},
+			OpCodeEvent::HouseDelete(data) => {self.remove_house(data.house_id);},
+			OpCodeEvent::PresenceUpdate(data) => {
+				let mut houses = self.houses.write().unwrap();
+				let houses = houses.values_mut()
+					.filter(|house| data.house_id.is_none_or(|house_id| house.id == house_id));
+				for member in houses.flat_map(|house| &mut house.members)
+						.filter(|member| member.user.id == data.user_id) {
+					member.presence = Some(data.presence);
+				}
+			},
+			OpCodeEvent::TypingStart(_) | OpCodeEvent::MessageCreate(_) |
+				OpCodeEvent::MessagePinned(_) | OpCodeEvent::MessageUnpinned(_) => ()
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Cache, RESPONSE_CAPACITY};
+	use crate::{
+		data::{House, Presence},
+		gateway::{EventHouseLeave, EventPresenceUpdate, OpCodeEvent}
+	};
+	use serde_json::{from_value, json};
+
+	fn house() -> House {
+		from_value(json!({
+			"name": "A House",
+			"icon": null,
+			"members": [{"user": {"username": "one", "name": "One", "icon": null,
+				"header": null, "id": "1"}}],
+			"rooms": [{"name": "general", "description": null, "position": 0,
+				"id": "11"}],
+			"id": "10",
+			"owner_id": "1"
+		})).unwrap()
+	}
+
+	#[test]
+	fn insert_test() {
+		let cache = Cache::new();
+		cache.insert_house(house());
+
+		assert_eq!(cache.house(10).unwrap().name, "A House");
+		assert_eq!(cache.room(11).unwrap().name, "general");
+		assert_eq!(cache.room_house_id(11), Some(10.into()));
+		assert_eq!(cache.house_ids(), vec![10]);
+	}
+
+	#[test]
+	fn eviction_test() {
+		let cache = Cache::new();
+		cache.insert_house(house());
+
+		assert!(cache.remove_house(10).is_some());
+		assert!(cache.house(10).is_none());
+		assert!(cache.room(11).is_none());
+		assert!(cache.room_house_id(11).is_none());
+		assert!(cache.remove_house(10).is_none());
+	}
+
+	#[test]
+	fn house_leave_test() {
+		let cache = Cache::new();
+		cache.update(&OpCodeEvent::HouseJoin(house()));
+		assert!(cache.house(10).is_some());
+
+		cache.update(&OpCodeEvent::HouseLeave(EventHouseLeave {house_id: 10.into()}));
+		assert!(cache.house(10).is_none());
+		assert!(cache.room(11).is_none());
+	}
+
+	#[test]
+	fn response_capacity_test() {
+		let cache = Cache::new();
+		for index in 0..=RESPONSE_CAPACITY {
+			cache.insert_response(format!("/houses/{}", index), "etag".to_owned(), vec![]);
+		}
+		// Replacing a response doesn't count as another url.
+		cache.insert_response("/houses/1".to_owned(), "newer".to_owned(), vec![]);
+
+		assert!(cache.response("/houses/0").is_none());
+		assert_eq!(cache.response("/houses/1").unwrap().0, "newer");
+		assert!(cache.response(&format!("/houses/{}", RESPONSE_CAPACITY)).is_some());
+		assert_eq!(cache.responses.read().unwrap().bodies.len(), RESPONSE_CAPACITY);
+	}
+
+	#[test]
+	fn presence_update_test() {
+		let cache = Cache::new();
+		cache.insert_house(house());
+		assert_eq!(cache.house(10).unwrap().is_online(1), None);
+
+		let update = |presence, house_id| OpCodeEvent::PresenceUpdate(EventPresenceUpdate {
+			user_id: 1.into(),
+			presence,
+			house_id
+		});
+		cache.update(&update(Presence::Online, None));
+		assert_eq!(cache.house(10).unwrap().is_online(1), Some(true));
+		// Updates for other houses are left to them.
+		cache.update(&update(Presence::Offline, Some(20.into())));
+		assert_eq!(cache.house(10).unwrap().is_online(1), Some(true));
+		cache.update(&update(Presence::Offline, Some(10.into())));
+		assert_eq!(cache.house(10).unwrap().is_online(1), Some(false));
+	}
+}
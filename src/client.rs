@@ -1,42 +1,129 @@
+#[cfg(feature = "cache")]
+use self::super::cache::Cache;
 use self::super::{
-	data::{House, Message},
+	data::{
+		AuditAction, AuditEntry, Category, Embed, Emoji, House, HouseId, Message,
+		MessageFlags, MessageId, PermissionOverride, Role, Room, RoomId, Snowflake,
+		User, UserId
+	},
 	gateway::{
-		EventInitState, EventTypingStart,
-		Frame,
-		OpCodeEvent, OpCodeHello, OpCodeLogin
+		AUTH_FAILED_CLOSE_CODE,
+		EventInitState, EventHouseDelete, EventHouseLeave, EventMessagePin,
+		EventPresenceUpdate, EventTypingStart,
+		Frame, GatewayBotInfo,
+		OpCodeEvent, OpCodeHello, OpCodeLogin,
+		OutgoingFrame, TYPING_TIMEOUT
 	},
 	http::{
 		PathInfo,
-		RequestInfo, RequestBodyInfo
-	}
+		RequestInfo, RequestBodyInfo,
+		ResponseInfo, RolePosition
+	},
+	markdown::split_message,
+	util::encode_image
 };
 use async_tungstenite::{
-	tokio::connect_async as websocket_async,
+	WebSocketStream,
+	tokio::{ConnectStream, connect_async as websocket_async},
 	tungstenite::{
+		Error as WebsocketError,
 		Message as WebsocketMessage,
 		protocol::frame::CloseFrame
 	}
 };
-use futures::{sink::SinkExt, stream::StreamExt};
-use reqwest::Client as HTTPClient;
-use serde_json::{from_str as from_json, to_string as to_json};
+use futures::{
+	sink::SinkExt,
+	stream::{FuturesUnordered, Stream, StreamExt, iter as stream_iter, unfold}
+};
+use rand::random;
+use reqwest::{
+	Client as HTTPClient, Error as HTTPError, Method, Response as HTTPResponse,
+	StatusCode, Url
+};
+use serde::de::DeserializeOwned;
+use serde_json::{
+	Error as JSONError, Value as JSONValue,
+	from_slice as from_json_slice, from_str as from_json, to_string as to_json
+};
 use std::{
+	cmp::Reverse,
+	collections::VecDeque,
+	env,
+	ops::{Deref, DerefMut},
 	fmt::Debug,
-	future::{Future, ready},
+	future::{Future, pending, ready},
+	io::Error as IOError,
 	pin::Pin,
 	result::Result as STDResult,
-	sync::Arc,
+	sync::{Arc, Mutex, RwLock, atomic::{AtomicBool, AtomicU64, Ordering}},
 	thread::{JoinHandle, spawn},
-	time::Duration
+	time::{Duration, Instant}
 };
+#[cfg(feature = "record-frames")]
+use std::path::PathBuf;
+#[cfg(feature = "record-frames")]
+use tokio::{fs::OpenOptions, io::AsyncWriteExt};
 use tokio::{
 	join, select,
-	sync::{Notify, mpsc::{Receiver, Sender, channel, error::SendError}},
-	time::timeout
+	signal::ctrl_c,
+	sync::{
+		Notify,
+		broadcast::{
+			RecvError as BroadcastRecvError, Sender as BroadcastSender,
+			channel as broadcast_channel
+		},
+		mpsc::{Receiver, Sender, channel, error::SendError},
+		oneshot::{Sender as OneshotSender, channel as oneshot_channel},
+		watch::{
+			Receiver as WatchReceiver, Sender as WatchSender, channel as watch_channel
+		}
+	},
+	runtime::Handle,
+	time::{Instant as TokioInstant, Interval, delay_for, interval_at, timeout}
 };
 
 type Result<T> = STDResult<T, Error>;
 
+/// The `User-Agent` sent with every api request, unless overridden with
+/// [`ClientBuilder::user_agent`].
+pub const USER_AGENT: &str = concat!("hiven-rs/", env!("CARGO_PKG_VERSION"));
+
+/// The longest message, in characters, that can be sent in one go.
+pub const MAX_MESSAGE_LENGTH: usize = 2000;
+
+/// The longest name, in characters, that can be given to something in a house,
+/// such as a category. See [`validate_name`].
+///
+/// Hiven doesn't publish its name limits, so this is a conservative guess
+/// rather than a documented number: a name that passes shouldn't be refused
+/// by hiven, but hiven may allow longer ones. It's checked everywhere the
+/// crate names something in a house, which so far is
+/// [`Client::create_category`] and [`Client::edit_category`]. There are no
+/// methods to create or rename houses, rooms or roles yet, and they should
+/// check names with [`validate_name`] too once there are.
+pub const MAX_NAME_LENGTH: usize = 32;
+
+/// The longest slowmode, in seconds, that can be set with
+/// [`Client::set_slowmode`]. That's 6 hours.
+pub const MAX_SLOWMODE: u32 = 6 * 60 * 60;
+
+const OUTGOING_CAPACITY: usize = 64;
+const OUTGOING_TTL: Duration = Duration::from_secs(30);
+
+// The longest a rate limited request waits before it's tried again. Past
+// this, it's given up on.
+const RATE_LIMIT_MAX_DELAY: Duration = Duration::from_secs(32);
+// How many times a rate limited request is tried again before it's given up
+// on. Backing off from a second, the last wait is RATE_LIMIT_MAX_DELAY.
+const RATE_LIMIT_MAX_RETRIES: u32 = 6;
+// How often Client::keep_typing sends the typing indicator again, a couple of
+// seconds before hiven lets it expire.
+const TYPING_INTERVAL: Duration = Duration::from_secs(TYPING_TIMEOUT.as_secs() - 2);
+// How many houses Client::leave_all_houses leaves at the same time.
+const LEAVE_CONCURRENCY: usize = 4;
+// How many events are kept for consumers that fall behind.
+const EVENT_BUFFER: usize = 256;
+
 /// Authentication of a user on hiven.
 ///
 /// With authentication of a user, you can call API endpoints as that user, or
@@ -53,7 +140,7 @@ type Result<T> = STDResult<T, Error>;
 /// - Go to the network tab on the Developer Tools window
 /// - Start typing in the room
 /// - Select the new `typing` request that appears. If two show up, select
-/// 	the one with a 200 status code
+///   the one with a 200 status code
 /// - Look for the `authorization` header under Request Headers, under Headers
 /// - The long string to the right is your token
 ///
@@ -62,36 +149,237 @@ type Result<T> = STDResult<T, Error>;
 /// with your password.** Another thing to keep in mind; it's always good
 /// etiquette to automate seperate accounts, dedicated for automation, rather
 /// than your own.
-pub struct Client<'u, 't> {
-	token: &'t str,
-	domains: (&'u str, &'u str),
-	http_client: HTTPClient
+pub struct Client {
+	// Shared with tasks like Client::keep_typing, so they see new tokens.
+	token: Arc<RwLock<String>>,
+	token_type: TokenType,
+	// Tells gateway connections to log in again, such as with a new token.
+	relogins: BroadcastSender<()>,
+	// Every event from the gateway, for the event handler and anything else
+	// listening in, like Client::await_message.
+	events: BroadcastSender<OpCodeEvent>,
+	domains: (String, String),
+	api_version: u32,
+	// Where api paths are appended, including the scheme and version.
+	api_base: String,
+	api_scheme: &'static str,
+	gateway_scheme: &'static str,
+	http_client: HTTPClient,
+	event_concurrency: usize,
+	connect_timeout: Duration,
+	gateway: GatewayConfig,
+	connected: AtomicBool,
+	// The id of the user the gateway last logged in as, or 0 before then.
+	user_id: AtomicU64,
+	stats: GatewayStats,
+	// Frames waiting to be sent once a gateway connection is logged in.
+	outgoing: Mutex<VecDeque<(Instant, Frame)>>,
+	outgoing_ready: Notify,
+	#[cfg(feature = "cache")]
+	cache: Cache,
+	#[cfg(feature = "record-frames")]
+	frame_record: Option<PathBuf>
 }
 
-impl<'u, 't> Client<'u, 't> {
+impl Client {
 	/// Creates a new client with an authentication token. Uses the official
 	/// hiven.io servers.
-	pub fn new(token: &'t str) -> Self {
-		Self {
-			token: token,
-			domains: ("api.hiven.io", "swarm-dev.hiven.io"),
-			http_client: HTTPClient::new()
-		}
+	pub fn new<T>(token: T) -> Self
+			where T: Into<String> {
+		ClientBuilder::new(token).try_build().expect("the official domains are valid")
 	}
 
 	/// Creates a new client with an authentication token, allows you to specify
 	/// a base domain for the api and gateway.
-	pub fn new_at(token: &'t str, api_base: &'u str, gateway_base: &'u str) ->
-			Self {
-		Self {
-			token: token,
-			domains: (api_base, gateway_base),
-			http_client: HTTPClient::new()
+	///
+	/// Returns [`Error::InvalidDomain`] if either domain isn't valid, see
+	/// [`ClientBuilder::try_build`].
+	pub fn new_at<T, A, G>(token: T, api_base: A, gateway_base: G) -> Result<Self>
+			where T: Into<String>, A: Into<String>, G: Into<String> {
+		ClientBuilder::new(token).domains(api_base, gateway_base).try_build()
+	}
+
+	/// Creates a client from the environment. The token is read from
+	/// `HIVEN_TOKEN`, and the domains from `HIVEN_API_HOST` and
+	/// `HIVEN_GATEWAY_HOST`, falling back to the official servers for either
+	/// that isn't set.
+	///
+	/// Returns [`Error::MissingToken`] if `HIVEN_TOKEN` isn't set or is empty,
+	/// and [`Error::InvalidDomain`] if a domain isn't valid.
+	pub fn from_env() -> Result<Self> {
+		Self::from_env_with(|name| env::var(name).ok())
+	}
+
+	/// Like [`from_env`](Client::from_env), but the variables are looked up
+	/// with `lookup`, for config that's kept somewhere other than the
+	/// process's environment.
+	pub fn from_env_with<L>(lookup: L) -> Result<Self>
+			where L: Fn(&str) -> Option<String> {
+		let token = lookup("HIVEN_TOKEN").filter(|token| !token.is_empty())
+			.ok_or(Error::MissingToken)?;
+
+		let mut builder = ClientBuilder::new(token);
+		if let Some(api_base) = lookup("HIVEN_API_HOST") {builder.domains.0 = api_base}
+		if let Some(gateway_base) = lookup("HIVEN_GATEWAY_HOST") {
+			builder.domains.1 = gateway_base;
+		}
+		builder.try_build()
+	}
+
+	/// The domain api requests are sent to.
+	pub fn api_host(&self) -> &str {
+		&self.domains.0
+	}
+
+	/// The domain the gateway connects to.
+	pub fn gateway_host(&self) -> &str {
+		&self.domains.1
+	}
+
+	/// Points the client at other domains for the api and gateway, for
+	/// instance to go through a debugging proxy. Returns
+	/// [`Error::InvalidDomain`], leaving the domains as they were, if either
+	/// isn't a valid host, see [`ClientBuilder::try_build`]. A domain without a
+	/// scheme keeps the one the client was already using for it.
+	///
+	/// This needs the client to not be borrowed, so it can't happen while the
+	/// gateway is running. The next connection uses the new gateway domain.
+	pub fn set_domains<A, G>(&mut self, api_base: A, gateway_base: G) -> Result<()>
+			where A: Into<String>, G: Into<String> {
+		let ((api_tls, api_host), (gateway_tls, gateway_host)) =
+			(normalize_domain(api_base.into())?, normalize_domain(gateway_base.into())?);
+
+		self.api_scheme = api_tls.map_or(self.api_scheme, api_scheme);
+		self.gateway_scheme = gateway_tls.map_or(self.gateway_scheme, gateway_scheme);
+		self.api_base = api_url(self.api_scheme, &api_host, self.api_version);
+		self.domains = (api_host, gateway_host);
+		Ok(())
+	}
+
+	/// Whether the client has a token to authenticate with. The token itself
+	/// isn't exposed, so it can't leak into logs by accident.
+	pub fn token_present(&self) -> bool {
+		!self.token.read().unwrap().is_empty()
+	}
+
+	/// Replaces the token the client authenticates with, for rotating
+	/// credentials without restarting.
+	///
+	/// Api requests made after this use the new token straight away. Gateway
+	/// connections that are up are closed and log back in with it, without
+	/// counting as a disconnect, whether or not the client is set to
+	/// [reconnect](GatewayConfig::reconnect).
+	pub fn set_token(&self, token: String) {
+		*self.token.write().unwrap() = token;
+		// Sending only fails if no gateway is connected, which is fine.
+		let _ = self.relogins.send(());
+	}
+
+	/// Closes gateway connections that are up and has them connect and log in
+	/// again, for when a bot knows its state has gone stale. This doesn't
+	/// count as a disconnect, and works whether or not the client is set to
+	/// [reconnect](GatewayConfig::reconnect). Does nothing if no gateway is
+	/// connected.
+	pub fn reconnect(&self) {
+		// Sending only fails if no gateway is connected, which is fine.
+		let _ = self.relogins.send(());
+	}
+
+	// The token as it's sent, in the authorization header and when logging in.
+	fn token(&self) -> String {
+		self.token_type.authorization(&self.token.read().unwrap())
+	}
+
+	/// Whether a gateway connection is currently up and logged in. This becomes
+	/// true once hiven sends the initial state, which is then passed to
+	/// [`EventHandler::on_connect`], and false again as soon as the connection
+	/// is lost.
+	pub fn is_connected(&self) -> bool {
+		self.connected.load(Ordering::Acquire)
+	}
+
+	/// The id of the user the client is logged in as, learned from the gateway.
+	/// `None` until the gateway has logged in once.
+	pub fn current_user_id(&self) -> Option<UserId> {
+		match self.user_id.load(Ordering::Acquire) {
+			0 => None,
+			user_id => Some(user_id.into())
 		}
 	}
 
+	/// How many times the gateway has tried to connect, including the first
+	/// attempt.
+	pub fn connection_attempts(&self) -> u64 {
+		self.stats.connection_attempts.load(Ordering::Acquire)
+	}
+
+	/// How many times the gateway has logged back in after the first time.
+	pub fn reconnects(&self) -> u64 {
+		self.stats.logins.load(Ordering::Acquire).saturating_sub(1)
+	}
+
+	/// Why the gateway connection was last lost, if it ever was.
+	pub fn last_disconnect_reason(&self) -> Option<String> {
+		self.stats.last_disconnect.lock().unwrap().clone()
+	}
+
+	/// How long the gateway has been connected for, since it last logged in.
+	/// `None` while it isn't connected.
+	pub fn uptime(&self) -> Option<Duration> {
+		self.stats.connected_at.lock().unwrap().map(|at| at.elapsed())
+	}
+
+	/// Queues a frame to be sent over the gateway. If the gateway is
+	/// reconnecting, the frame waits until it's logged back in, so updates
+	/// aren't lost to a brief outage.
+	///
+	/// At most 64 frames are kept, dropping the oldest first, and frames that
+	/// waited longer than 30 seconds are dropped as stale. Event frames are
+	/// only ever sent by hiven, and are ignored.
+	pub fn send_frame(&self, frame: Frame) {
+		if let Frame::Event(_) = frame {return}
+
+		let mut outgoing = self.outgoing.lock().unwrap();
+		if outgoing.len() >= OUTGOING_CAPACITY {outgoing.pop_front();}
+		outgoing.push_back((Instant::now(), frame));
+		self.outgoing_ready.notify();
+	}
+
+	/// Sends a frame, such as a presence update, over the gateway. Returns
+	/// [`Error::NotConnected`] if the gateway isn't logged in, rather than
+	/// queueing the frame like [`send_frame`](Client::send_frame) does.
+	///
+	/// ```rust,no_run
+	/// use hiven_rs::{data::Presence, gateway::OutgoingFrame};
+	///
+	/// # fn example(client: &hiven_rs::Client) -> Result<(), hiven_rs::client::Error> {
+	/// client.gateway_send(OutgoingFrame::Presence(Presence::Online))?;
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn gateway_send<F>(&self, frame: F) -> Result<()>
+			where F: Into<OutgoingFrame> {
+		if !self.is_connected() {return Err(Error::NotConnected)}
+		self.send_frame(Frame::from(frame.into()));
+		Ok(())
+	}
+
+	// Takes the queued frames that aren't stale yet.
+	fn take_outgoing(&self) -> Vec<Frame> {
+		self.outgoing.lock().unwrap().drain(..)
+			.filter(|(queued, _)| queued.elapsed() < OUTGOING_TTL)
+			.map(|(_, frame)| frame).collect()
+	}
+
+	/// The cache of houses the client is in, kept up to date by the gateway.
+	/// Only available with the `cache` feature.
+	#[cfg(feature = "cache")]
+	pub fn cache(&self) -> &Cache {
+		&self.cache
+	}
+
 	pub async fn new_gate_keeper<'c, E>(&'c self, event_handler: E) ->
-			GateKeeper<'c, 'u, 't, E>
+			GateKeeper<'c, E>
 				where E: EventHandler {
 		GateKeeper::new(self, event_handler)
 	}
@@ -113,264 +401,2961 @@ impl<'u, 't> Client<'u, 't> {
 	/// # struct MyEventHandler;
 	/// #
 	/// impl EventHandler for MyEventHandler {
-	/// 	fn on_message<'c>(&self, client: &'c Client, event: Message) ->
-	/// 			Pin<Box<dyn Future<Output = ()> + 'c>> {
-	/// 		Box::pin(async move {
-	/// 			// Asynchronous code goes here.
-	/// 		})
-	/// 	}
+	///     fn on_message<'c>(&self, client: &'c Client, event: Message) ->
+	///             Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+	///         Box::pin(async move {
+	///             // Asynchronous code goes here.
+	///         })
+	///     }
 	/// }
 	/// ```
 	///
-	/// This method currently is not expected to return ever, unless during panic
-	/// unwinding, and it's return value should be treated as `!` (the never
-	/// type).
-	//
-	// Notes For Next Minor Version
-	// ----------------------------
-	// In the next minor version, this method will be renamed and it's signature
-	// will be changed to support returning of Errors, or a unit, with
-	// the introduction of a stop function.
-	pub async fn start_gateway<E>(&self, event_handler: E) -> Result<()>
+	/// The returned [`GatewayExit`] says why the gateway stopped, such as the
+	/// token being rejected, so supervising code can decide whether to start it
+	/// again.
+	pub async fn start_gateway<E>(&self, event_handler: E) -> GatewayExit
 			where E: EventHandler {
 		let gate_keeper = GateKeeper::new(self, event_handler);
 		gate_keeper.start_gateway().await
 	}
 
-	pub async fn send_message<R>(&self, room: R, content: String)
-			where R: Into<u64> {
-		execute_request(&self.http_client, RequestInfo {
-			token: self.token.to_owned(),
-			path: PathInfo::MessageSend {
-				channel_id: room.into()
-			},
-			body: RequestBodyInfo::MessageSend {
-				content: content
+	/// Like [`start_gateway`], but closes the connection gracefully when the
+	/// process gets Ctrl-C (SIGINT), and returns once running event handlers
+	/// have finished.
+	///
+	/// If Ctrl-C can't be listened for on this platform, the gateway keeps
+	/// running as if this were [`start_gateway`].
+	///
+	/// [`start_gateway`]: Client::start_gateway
+	pub async fn start_gateway_until_ctrl_c<E>(&self, event_handler: E) ->
+			GatewayExit
+				where E: EventHandler {
+		let gate_keeper = GateKeeper::new(self, event_handler);
+		let shutdown = gate_keeper.shutdown_handle();
+		let mut gateway = Box::pin(gate_keeper.start_gateway());
+
+		select! {
+			exit = &mut gateway => exit,
+			result = ctrl_c() => {
+				if result.is_ok() {shutdown.shutdown()}
+				gateway.await
 			}
-		}, self.domains.0).await;
+		}
 	}
 
-	pub async fn trigger_typing<R>(&self, room: R)
-			where R: Into<u64> {
-		execute_request(&self.http_client, RequestInfo {
-			token: self.token.to_owned(),
-			path: PathInfo::TypingTrigger {
-				channel_id: room.into()
+	/// Connects to the gateway and waits for hiven to send the initial state,
+	/// for scripts that want to do a few things and leave, rather than react to
+	/// events.
+	///
+	/// Events after the initial state are ignored. The connection is driven by
+	/// the returned [`ReadyHandle`], so it only makes progress (heart beats
+	/// included) while the handle is being awaited. Hiven drops connections that
+	/// miss heart beats, so don't hold on to an idle handle for longer than the
+	/// heart beat interval.
+	///
+	/// ```rust,no_run
+	/// # async fn example() -> Result<(), hiven_rs::client::Error> {
+	/// let client = hiven_rs::Client::new("token");
+	/// let (init_state, handle) = client.connect_and_wait_ready().await?;
+	///
+	/// println!("logged in as {}", init_state.user.username);
+	/// handle.shutdown().await;
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub async fn connect_and_wait_ready(&self) ->
+			Result<(EventInitState, ReadyHandle<'_>)> {
+		let (sender, mut receiver) = channel(1);
+		let gate_keeper = GateKeeper::new(self, ReadyHandler(sender));
+		let shutdown = gate_keeper.shutdown_handle();
+		let mut gateway: Pin<Box<dyn Future<Output = GatewayExit> + Send + '_>> =
+			Box::pin(async move {gate_keeper.start_gateway().await});
+
+		select! {
+			exit = &mut gateway => match exit {
+				GatewayExit::Fatal(err) => Err(err),
+				exit => Err(Error::expectation_failed(
+					"Frame::Event(OpCodeEvent::InitState(...))", exit))
 			},
-			body: RequestBodyInfo::TypingTrigger {}
-		}, self.domains.0).await;
+			// The handler lives as long as the gateway, so this can't be None.
+			Some(init_state) = receiver.next() => Ok((init_state, ReadyHandle {
+				gateway,
+				shutdown
+			}))
+		}
 	}
-}
 
-impl Client<'static, 'static> {
-	pub fn start_gateway_later<E>(self: Arc<Self>, event_handler: E) ->
-			JoinHandle<()>
-				where E: EventHandler + 'static {
-		spawn(move || {
-			let gate_keeper = GateKeeper::new(&self, event_handler);
-			let mut runtime = tokio::runtime::Runtime::new().unwrap();
-			runtime.block_on(async {
-				gate_keeper.start_gateway().await.unwrap();
-			});
+	/// Waits for the next message that matches `predicate`, for interactive
+	/// bots that need a reply, like a confirmation. Returns `None` if no message
+	/// matched within `timeout`.
+	///
+	/// Only messages a running gateway receives after this is called are seen,
+	/// and they're still passed to the event handler as usual.
+	///
+	/// ```rust,no_run
+	/// # use hiven_rs::data::{RoomId, UserId};
+	/// # async fn example(client: &hiven_rs::Client, room_id: RoomId, user_id: UserId) {
+	/// use std::time::Duration;
+	///
+	/// let yes = client.await_message(|message| message.room_id == room_id
+	///     && message.author_id == user_id && message.content == "yes",
+	///     Duration::from_secs(30)).await;
+	/// # }
+	/// ```
+	pub async fn await_message<P>(&self, predicate: P, timeout: Duration) ->
+			Option<Message>
+				where P: Fn(&Message) -> bool {
+		let mut events = self.events.subscribe();
+		let wait = async {
+			loop {
+				match events.recv().await {
+					Ok(OpCodeEvent::MessageCreate(message)) if predicate(&message) =>
+						break Some(message),
+					// Messages missed by falling behind can't be checked anyway.
+					Ok(_) | Err(BroadcastRecvError::Lagged(_)) => continue,
+					Err(BroadcastRecvError::Closed) => break None
+				}
+			}
+		};
+
+		tokio::time::timeout(timeout, wait).await.ok().flatten()
+	}
+
+	/// Listens in on every event the gateway receives from now on, alongside the
+	/// event handler. The stream keeps going across reconnects, and only ends
+	/// when the client is dropped.
+	///
+	/// Events are buffered for consumers that fall behind, up to a point. Past
+	/// that the oldest ones are skipped, and the stream yields
+	/// [`Error::EventsLagged`] with how many.
+	pub fn events(&self) -> impl Stream<Item = Result<OpCodeEvent>> {
+		// Subscribed right away, so no events are missed before the first poll.
+		unfold(self.events.subscribe(), |mut events| async move {
+			match events.recv().await {
+				Ok(event) => Some((Ok(event), events)),
+				Err(BroadcastRecvError::Lagged(skipped)) =>
+					Some((Err(Error::EventsLagged(skipped)), events)),
+				Err(BroadcastRecvError::Closed) => None
+			}
 		})
 	}
-}
 
-async fn execute_request<'a>(client: &HTTPClient, request: RequestInfo,
-		base_url: &'a str) {
-	let path = format!("https://{}/v1{}", base_url, request.path.path());
-	let http_request = client.request(request.body.method(), &path)
-		.header("authorization", request.token);
+	pub async fn send_message<R, C>(&self, room: R, content: C) -> Result<Message>
+			where R: Into<RoomId>, C: Into<String> {
+		self.send_message_with(room, MessageBuilder::new(content)).await
+	}
+
+	/// Sends a message built with a [`MessageBuilder`], allowing options such
+	/// as message flags to be set.
+	pub async fn send_message_with<R>(&self, room: R, message: MessageBuilder) ->
+			Result<Message>
+				where R: Into<RoomId> {
+		if let Some(url) = message.attachment_urls.iter()
+				.find(|url| !is_web_url(url)) {
+			return Err(Error::Validation(format!("{:?} isn't an http or https url", url)))
+		}
 
-	let http_request = if request.body.method() != "GET" {
-		http_request.header("content-type", "application/json")
-			.body(to_json(&request.body).unwrap()) // Remove unwrap().
-	} else {http_request};
-	
-	// Remove unwrap()s.
-	http_request.send().await.unwrap().error_for_status().unwrap();
-}
+		execute_request_data(&self.http_client, RequestInfo {
+			token: self.token(),
+			path: PathInfo::MessageSend {
+				channel_id: room.into()
+			},
+			body: RequestBodyInfo::MessageSend {
+				content: message.content,
+				flags: message.flags,
+				nonce: message.nonce,
+				sticker_id: message.sticker_id.map(|id| id.to_string()),
+				attachment_urls: message.attachment_urls,
+				embed: message.embed
+			}
+		}, &self.api_base).await
+	}
 
-// These lifetimes and this generic are a special set of generics, they are able
-// to describe the person reading them with 100% accuracy.
-//
-// I promise this wasn't intended, but now I love it.
-pub struct GateKeeper<'c, 'u, 't, E>
-		where E: EventHandler {
-	pub client: &'c Client<'u, 't>,
-	pub event_handler: E
-}
+	/// Sends content that may be too long for a single message, splitting it
+	/// into as many messages as needed. Splits happen at line breaks or
+	/// spaces where possible, and code blocks are kept whole unless they're
+	/// too long for a message by themselves.
+	///
+	/// Messages are sent in order, and sending stops at the first error.
+	pub async fn send_message_chunked<R, C>(&self, room: R, content: C) ->
+			Result<Vec<Message>>
+				where R: Into<RoomId>, C: Into<String> {
+		let (room, content) = (room.into(), content.into());
+		let mut messages = Vec::new();
 
-impl<'c, 'u, 't, E> GateKeeper<'c, 'u, 't, E>
-		where E: EventHandler {
-	pub fn new(client: &'c Client<'u, 't>, event_handler: E) -> Self {
-		Self {
-			client: client,
-			event_handler: event_handler
+		for chunk in split_message(&content, MAX_MESSAGE_LENGTH) {
+			messages.push(self.send_message(room, chunk).await?);
 		}
+
+		Ok(messages)
 	}
 
-	pub async fn start_gateway(&self) -> Result<()> {
-		let (outgoing_send, outgoing_receive) = channel(5);
-		let (incoming_send, incoming_receive) = channel(5);
+	/// Sends several messages to a room one after the other, for bots that
+	/// post a lot at once, like a digest.
+	///
+	/// When hiven rate limits a message with a 429 status, sending waits as
+	/// long as hiven's `Retry-After` header asks, or backs off from a second
+	/// when it doesn't say, and tries it again. A message is given up on after
+	/// 6 tries again, or once it would have to wait over 32 seconds, so
+	/// backing off takes about a minute at most. That, or any other error,
+	/// stops sending, and is returned as [`Error::PartiallySent`] with the
+	/// messages that made it.
+	pub async fn send_many<R, I>(&self, room: R, contents: I) -> Result<Vec<Message>>
+			where R: Into<RoomId>, I: IntoIterator, I::Item: Into<String> {
+		let room = room.into();
+		let mut messages = Vec::new();
 
-		match join!(
-			self.manage_gateway(incoming_send, outgoing_receive),
-			self.listen_gateway(incoming_receive, outgoing_send)
-		) {
-			(Ok(()), Ok(())) => Ok(()),
-			(Err(err), Ok(())) => Err(err),
-			(Ok(()), Err(err)) => Err(err),
-			(Err(_), Err(err)) => Err(err)
+		for content in contents {
+			// The same builder is sent each time, so retries keep the nonce.
+			let message = MessageBuilder::new(content);
+
+			match retry_rate_limited(|| self.send_message_with(room, message.clone())).await {
+				Ok(message) => messages.push(message),
+				Err(err) => return Err(Error::PartiallySent(messages, Box::new(err)))
+			}
 		}
+
+		Ok(messages)
 	}
 
-	async fn manage_gateway(&self, mut sender: Sender<Frame>,
-			mut receiver: Receiver<Option<Frame>>) -> Result<()> {
-		let url = format!("wss://{}/socket", self.client.domains.1);
-		let mut socket = websocket_async(url).await.unwrap().0; // Remove unwrap().
+	/// Edits a message previously sent by this user. Only the parts set on
+	/// `edit` are changed, so an embed can be updated without touching the
+	/// content, and vice versa.
+	pub async fn edit_message<R, M>(&self, room: R, message_id: M,
+			edit: MessageEditBuilder) -> Result<()>
+				where R: Into<RoomId>, M: Into<MessageId> {
+		execute_request(&self.http_client, RequestInfo {
+			token: self.token(),
+			path: PathInfo::MessageEdit {
+				channel_id: room.into(),
+				message_id: message_id.into()
+			},
+			body: RequestBodyInfo::MessageEdit {
+				content: edit.content,
+				embed: edit.embed
+			}
+		}, &self.api_base).await?;
+		Ok(())
+	}
 
-		loop {
-			let incoming_frame = socket.next();
-			let outgoing_frame = receiver.next();
+	/// Fetches a single message by its id. If the message doesn't exist
+	/// (anymore), this returns [`Error::Http`] with a 404 status.
+	pub async fn get_message<R, M>(&self, room: R, message_id: M) -> Result<Message>
+			where R: Into<RoomId>, M: Into<MessageId> {
+		execute_request_data(&self.http_client, RequestInfo {
+			token: self.token(),
+			path: PathInfo::MessageGet {
+				channel_id: room.into(),
+				message_id: message_id.into()
+			},
+			body: RequestBodyInfo::MessageGet {}
+		}, &self.api_base).await
+	}
 
-			select! {
-				// Remove second unwrap().
-				// Consider removing first unwrap(). (Can tungstenite return a None
-				// before SocketClose?)
-				frame = incoming_frame => match frame.unwrap().unwrap() {
-					// Remove unwrap()s.
-					WebsocketMessage::Text(frame) => match from_json::<Frame>(&frame) {
-						
-						Ok(frame) => sender.send(frame).await.unwrap(),
-						// Uncomment to show events that can't yet be parsed.
-						// Err(err) => println!("{:?}: {}", err, frame),
-						_ => ()
-					},
-					WebsocketMessage::Close(close_data) => return Err(Error::socket_close(close_data)),
-					frame @ _ => return Err(Error::expectation_failed(
-						"Text or Close frames only", frame))
+	/// Fetches a page of a room's messages, newest first. Without `before`, the
+	/// page starts at the latest message, otherwise it starts at the message
+	/// right before the one with that id. An empty page means there are no
+	/// older messages.
+	pub async fn get_messages<R>(&self, room: R, before: Option<MessageId>) ->
+			Result<Vec<Message>>
+				where R: Into<RoomId> {
+		let mut messages: Vec<Message> = execute_request_data(&self.http_client,
+			RequestInfo {
+				token: self.token(),
+				path: PathInfo::MessageHistory {
+					channel_id: room.into(),
+					before
 				},
-				// Remove unwrap()s.
-				frame = outgoing_frame => socket.send(WebsocketMessage::Text(
-					to_json(&frame.flatten().unwrap()).unwrap())).await.unwrap()
+				body: RequestBodyInfo::MessageHistory {}
+			}, &self.api_base).await?;
+
+		messages.sort_by_key(|message| Reverse(message.id));
+		Ok(messages)
+	}
+
+	/// Walks backwards through a room's entire history, newest message first,
+	/// fetching pages with [`get_messages`] as they're needed. The stream ends
+	/// after the oldest message, or after the first error.
+	///
+	/// Pages are only fetched when the stream is polled past the end of the
+	/// last one, so the consumer sets the pace. A page hiven rate limits is
+	/// waited out and fetched again, like messages with [`send_many`].
+	///
+	/// ```rust,no_run
+	/// use futures::stream::StreamExt;
+	///
+	/// # async fn example(client: hiven_rs::Client) {
+	/// let mut messages = Box::pin(client.messages_iter(1_u64));
+	/// while let Some(message) = messages.next().await {
+	///     println!("{}", message.unwrap().content);
+	/// }
+	/// # }
+	/// ```
+	///
+	/// [`get_messages`]: Client::get_messages
+	/// [`send_many`]: Client::send_many
+	pub fn messages_iter<R>(&self, room: R) -> impl Stream<Item = Result<Message>> + '_
+			where R: Into<RoomId> {
+		let room = room.into();
+
+		// The state is the cursor and what's left of the current page, or None
+		// once there was an error.
+		unfold(Some((None, Vec::new())), move |state| async move {
+			let (before, mut page): (Option<MessageId>, Vec<Message>) = state?;
+			if page.is_empty() {
+				page = match retry_rate_limited(|| self.get_messages(room, before)).await {
+					Ok(page) => page,
+					Err(err) => return Some((Err(err), None))
+				};
+				// Oldest first, so messages can be popped off the end.
+				page.reverse();
 			}
-		}
+
+			let message = page.pop()?;
+			let before = Some(message.id);
+			Some((Ok(message), Some((before, page))))
+		})
 	}
 
-	async fn listen_gateway(&self, mut receiver: Receiver<Frame>,
-			mut sender: Sender<Option<Frame>>) -> Result<()> {
-		let notify = Notify::new();
+	/// Fetches every user who reacted to a message with an emoji, as for
+	/// picking the winner of a giveaway. The emoji is either a unicode emoji or
+	/// a custom one's name, as with [`clear_reactions_for_emoji`].
+	///
+	/// Hiven sends reactors in pages, which are all fetched before this
+	/// returns.
+	///
+	/// [`clear_reactions_for_emoji`]: Client::clear_reactions_for_emoji
+	pub async fn get_reactions<R, M>(&self, room: R, message_id: M, emoji: &str) ->
+			Result<Vec<User>>
+				where R: Into<RoomId>, M: Into<MessageId> {
+		let (channel_id, message_id) = (room.into(), message_id.into());
+		let mut users: Vec<User> = Vec::new();
 
-		let heart_beat = match receiver.next().await {
-			// We got what we needed.
-			Some(Frame::Hello(OpCodeHello {heart_beat})) => {
-				let bag = (sender.clone(), Duration::from_millis(heart_beat.into()));
+		loop {
+			let after = users.last().map(|user| user.id);
+			let page: Vec<User> = execute_request_data(&self.http_client, RequestInfo {
+				token: self.token(),
+				path: PathInfo::ReactionUsers {
+					channel_id,
+					message_id,
+					emoji: emoji.to_owned(),
+					after
+				},
+				body: RequestBodyInfo::ReactionUsersGet {}
+			}, &self.api_base).await?;
 
-				// Set heart_beat to our heart beat future.
-				async {
-					let (mut sender, duration) = bag;
+			// Also stops if hiven ignored the cursor and sent the same page again.
+			if page.is_empty() || page.last().map(|user| user.id) == after {break}
+			users.extend(page);
+		}
 
-					loop {
-						// Remove unwrap().
-						if let Ok(()) = timeout(duration, notify.notified()).await
-							{return Result::Ok(())}
-						sender.send(Some(Frame::HeartBeat)).await.unwrap();
-					}
-				}
-			},
-			// Expectation failed.
-			Some(frame) => return Err(Error::expectation_failed(
-				"Frame::Hello(...)", frame)),
-			// The channel died, exit gracefully.
-			None => return Ok(())
-		};
+		Ok(users)
+	}
 
-		let login_frame = Frame::Login(OpCodeLogin {
-			token: self.client.token.to_owned()
-		});
-		sender.send(Some(login_frame)).await?;
+	/// Reacts to a message with an emoji, as the client's user.
+	pub async fn add_reaction<R, M>(&self, room: R, message_id: M, emoji: &str) ->
+			Result<()>
+				where R: Into<RoomId>, M: Into<MessageId> {
+		self.own_reaction(room.into(), message_id.into(), emoji,
+			RequestBodyInfo::ReactionAdd {})
+			.await
+	}
 
-		let listener = async {
-			let result = loop {
-				match receiver.next().await {
-					Some(Frame::Event(event)) => match event {
-						OpCodeEvent::InitState(data) =>
-							self.event_handler.on_connect(&self.client, data).await,
-						OpCodeEvent::HouseJoin(data) =>
-							self.event_handler.on_house_join(&self.client, data).await,
-						OpCodeEvent::TypingStart(data) =>
-							self.event_handler.on_typing(&self.client, data).await,
-						OpCodeEvent::MessageCreate(data) =>
-							self.event_handler.on_message(&self.client, data).await
-					},
-					// The channel died, exit gracefully.
-					None => break Result::Ok(()),
-					_ => unimplemented!() // Remove unimplemented!().
-				}
-			};
+	/// Takes back the client's user's reaction with an emoji from a message.
+	pub async fn remove_reaction<R, M>(&self, room: R, message_id: M, emoji: &str) ->
+			Result<()>
+				where R: Into<RoomId>, M: Into<MessageId> {
+		self.own_reaction(room.into(), message_id.into(), emoji,
+			RequestBodyInfo::ReactionRemove {}).await
+	}
 
-			notify.notify();
-			result
-		};
+	/// Adds the client's user's reaction with an emoji to a message if it
+	/// isn't there yet, and removes it if it is, like clicking on a reaction.
+	/// Returns whether the reaction is there afterwards.
+	///
+	/// Whether the user has reacted is checked with [`Client::get_reactions`],
+	/// so this returns [`Error::NotConnected`] until the gateway has logged in
+	/// and the user is known.
+	pub async fn toggle_reaction<R, M>(&self, room: R, message_id: M, emoji: &str) ->
+			Result<bool>
+				where R: Into<RoomId>, M: Into<MessageId> {
+		let (room, message_id) = (room.into(), message_id.into());
+		let user_id = self.current_user_id().ok_or(Error::NotConnected)?;
 
-		match join!(heart_beat, listener) {
-			(Ok(()), Ok(())) => Ok(()),
-			(Err(err), Ok(())) => Err(err),
-			(Ok(()), Err(err)) => Err(err),
-			(Err(_), Err(err)) => Err(err)
-		}
+		let reacted = self.get_reactions(room, message_id, emoji).await?.iter()
+			.any(|user| user.id == user_id);
+		if reacted {self.remove_reaction(room, message_id, emoji).await?}
+		else {self.add_reaction(room, message_id, emoji).await?}
+		Ok(!reacted)
 	}
-}
 
-#[derive(Debug)]
-pub enum Error {
-	ExpectationFailed(&'static str, String),
-	SocketClose(Option<CloseFrame<'static>>),
-	InternalChannelError(String)
-}
+	async fn own_reaction(&self, channel_id: RoomId, message_id: MessageId, emoji: &str,
+			body: RequestBodyInfo) -> Result<()> {
+		execute_request(&self.http_client, RequestInfo {
+			token: self.token(),
+			path: PathInfo::OwnReaction {
+				channel_id,
+				message_id,
+				emoji: emoji.to_owned()
+			},
+			body
+		}, &self.api_base).await?;
+		Ok(())
+	}
 
-impl Error {
-	pub fn expectation_failed<S>(expected: &'static str, got: S) -> Self
-			where S: Debug {
-		Self::ExpectationFailed(expected, format!("{:?}", got))
+	/// Removes every reaction from a message. Without permission to manage
+	/// messages in the room, this returns [`Error::Http`] with a 403 status.
+	pub async fn clear_reactions<R, M>(&self, room: R, message_id: M) -> Result<()>
+			where R: Into<RoomId>, M: Into<MessageId> {
+		self.delete_reactions(room.into(), message_id.into(), None).await
 	}
 
-	pub fn socket_close(close_data: Option<CloseFrame<'static>>) -> Self {
-		Self::SocketClose(close_data)
+	/// Removes every reaction with a certain emoji from a message. Without
+	/// permission to manage messages in the room, this returns [`Error::Http`]
+	/// with a 403 status.
+	pub async fn clear_reactions_for_emoji<R, M>(&self, room: R, message_id: M,
+			emoji: &str) -> Result<()>
+				where R: Into<RoomId>, M: Into<MessageId> {
+		self.delete_reactions(room.into(), message_id.into(), Some(emoji.to_owned()))
+			.await
 	}
 
-	pub fn send_error<T>(error: SendError<T>) -> Self
-			where T: Debug {
-		Self::InternalChannelError(format!("{:?}", error))
+	async fn delete_reactions(&self, channel_id: RoomId, message_id: MessageId,
+			emoji: Option<String>) -> Result<()> {
+		execute_request(&self.http_client, RequestInfo {
+			token: self.token(),
+			path: PathInfo::Reactions {
+				channel_id,
+				message_id,
+				emoji
+			},
+			body: RequestBodyInfo::ReactionsClear {}
+		}, &self.api_base).await?;
+		Ok(())
 	}
-}
 
-impl<T> From<SendError<T>> for Error
-		where T: Debug {
-	fn from(error: SendError<T>) -> Self {
-		Self::send_error(error)
+	/// Bans a user from a house, with the options set on `ban`. Without
+	/// permission to ban members, this returns [`Error::Http`] with a 403
+	/// status.
+	pub async fn ban_member<H, U>(&self, house: H, user_id: U, ban: BanBuilder) ->
+			Result<()>
+				where H: Into<HouseId>, U: Into<UserId> {
+		execute_request(&self.http_client, RequestInfo {
+			token: self.token(),
+			path: PathInfo::MemberBan {
+				house_id: house.into(),
+				user_id: user_id.into()
+			},
+			body: RequestBodyInfo::MemberBan {
+				reason: ban.reason,
+				delete_message_days: ban.delete_message_days
+			}
+		}, &self.api_base).await?;
+		Ok(())
 	}
-}
 
-pub trait EventHandler: Send {
-	fn on_connect<'c>(&self, _client: &'c Client<'c, 'c>, _event: EventInitState) -> Pin<Box<dyn Future<Output = ()> + 'c>> {
-		// NoOp
-		Box::pin(ready(()))
+	/// Gets a page of a house's audit log, newest entry first, with only the
+	/// entries that match `filter`. Older pages can be fetched by passing the
+	/// id of the oldest entry to [`AuditFilter::before`]. Without permission to
+	/// manage the house, this returns [`Error::Http`] with a 403 status.
+	///
+	/// Hiven doesn't document an audit log endpoint yet, so on instances that
+	/// don't have one this returns [`Error::Http`] with a 404 status.
+	pub async fn get_audit_log<H>(&self, house: H, filter: AuditFilter) ->
+			Result<Vec<AuditEntry>>
+				where H: Into<HouseId> {
+		let mut entries: Vec<AuditEntry> = execute_request_data(&self.http_client,
+			RequestInfo {
+				token: self.token(),
+				path: PathInfo::AuditLog {
+					house_id: house.into(),
+					action_type: filter.action_type.map(u64::from),
+					actor_id: filter.actor_id,
+					before: filter.before
+				},
+				body: RequestBodyInfo::AuditLogGet {}
+			}, &self.api_base).await?;
+
+		entries.sort_by_key(|entry| Reverse(entry.id));
+		Ok(entries)
 	}
 
-	fn on_house_join<'c>(&self, _client: &'c Client<'c, 'c>, _event: House) -> Pin<Box<dyn Future<Output = ()> + 'c>> {
-		// NoOp
-		Box::pin(ready(()))
+	/// Sets a member's nickname in a house, or clears it with `None`. The
+	/// client's own nickname can be set too. Without permission to manage
+	/// nicknames, this returns [`Error::Http`] with a 403 status.
+	pub async fn set_nickname<H, U>(&self, house: H, user_id: U, nick: Option<String>) ->
+			Result<()>
+				where H: Into<HouseId>, U: Into<UserId> {
+		// Hiven has separate permissions for changing your own nickname, which go
+		// through @me.
+		let user_id = user_id.into();
+		let user_id = match self.current_user_id() {
+			Some(current_user_id) if current_user_id == user_id => None,
+			_ => Some(user_id)
+		};
+
+		execute_request(&self.http_client, RequestInfo {
+			token: self.token(),
+			path: PathInfo::Member {
+				house_id: house.into(),
+				user_id
+			},
+			body: RequestBodyInfo::MemberEdit {nick}
+		}, &self.api_base).await?;
+		Ok(())
 	}
 
-	fn on_typing<'c>(&self, _client: &'c Client<'c, 'c>, _event: EventTypingStart) -> Pin<Box<dyn Future<Output = ()> + 'c>> {
-		// NoOp
-		Box::pin(ready(()))
+	/// Fetches a house by its id, along with its rooms and members. If the
+	/// client isn't in the house, this returns [`Error::Http`] with a 404
+	/// status.
+	pub async fn get_house<H>(&self, house: H) -> Result<House>
+			where H: Into<HouseId> {
+		self.execute_request_cached(RequestInfo {
+			token: self.token(),
+			path: PathInfo::House {
+				house_id: house.into()
+			},
+			body: RequestBodyInfo::HouseGet {}
+		}).await
 	}
 
-	fn on_message<'c>(&self, _client: &'c Client<'c, 'c>, _event: Message) -> Pin<Box<dyn Future<Output = ()> + 'c>> {
-		// NoOp
-		Box::pin(ready(()))
+	/// Fetches how hiven recommends connecting to the gateway: the url, how
+	/// many shards to use, and how many sessions may still be started. If hiven
+	/// doesn't have this for the account, it returns [`Error::Http`] with a 404
+	/// status.
+	pub async fn gateway_bot_info(&self) -> Result<GatewayBotInfo> {
+		execute_request_data(&self.http_client, RequestInfo {
+			token: self.token(),
+			path: PathInfo::GatewayBot,
+			body: RequestBodyInfo::GatewayBotGet {}
+		}, &self.api_base).await
+	}
+
+	/// Fetches every house the client is in.
+	pub async fn list_houses(&self) -> Result<Vec<House>> {
+		execute_request_data(&self.http_client, RequestInfo {
+			token: self.token(),
+			path: PathInfo::Houses,
+			body: RequestBodyInfo::HousesGet {}
+		}, &self.api_base).await
+	}
+
+	/// Leaves a house. The owner of a house can't leave it.
+	pub async fn leave_house<H>(&self, house: H) -> Result<()>
+			where H: Into<HouseId> {
+		execute_request(&self.http_client, RequestInfo {
+			token: self.token(),
+			path: PathInfo::Member {
+				house_id: house.into(),
+				user_id: None
+			},
+			body: RequestBodyInfo::HouseLeave {}
+		}, &self.api_base).await?;
+		Ok(())
+	}
+
+	/// Leaves every house the client is in, to clean up after test bots and
+	/// throwaway accounts.
+	///
+	/// A few houses are left at the same time, and rate limited requests are
+	/// tried again like with [`Client::send_many`]. A house that can't be left
+	/// doesn't stop the others, they're all returned together as
+	/// [`Error::HousesNotLeft`].
+	pub async fn leave_all_houses(&self) -> Result<()> {
+		let houses = self.list_houses().await?;
+		let failed: Vec<_> = stream_iter(houses)
+			.map(|house| async move {
+				(house.id, retry_rate_limited(|| self.leave_house(house.id)).await)
+			})
+			.buffer_unordered(LEAVE_CONCURRENCY)
+			.filter_map(|(house_id, result)| ready(result.err().map(|err| (house_id, err))))
+			.collect().await;
+
+		if failed.is_empty() {Ok(())}
+		else {Err(Error::HousesNotLeft(failed))}
+	}
+
+	/// Fetches a house's custom emojis.
+	pub async fn list_house_emojis<H>(&self, house: H) -> Result<Vec<Emoji>>
+			where H: Into<HouseId> {
+		self.execute_request_cached(RequestInfo {
+			token: self.token(),
+			path: PathInfo::Emojis {
+				house_id: house.into(),
+				emoji_id: None
+			},
+			body: RequestBodyInfo::EmojisGet {}
+		}).await
+	}
+
+	/// Adds a custom emoji to a house, from the bytes of a png, or a gif for an
+	/// animated one. Without permission to manage emojis, this returns
+	/// [`Error::Http`] with a 403 status.
+	pub async fn create_house_emoji<H, N>(&self, house: H, name: N, image: &[u8]) ->
+			Result<Emoji>
+				where H: Into<HouseId>, N: Into<String> {
+		execute_request_data(&self.http_client, RequestInfo {
+			token: self.token(),
+			path: PathInfo::Emojis {
+				house_id: house.into(),
+				emoji_id: None
+			},
+			body: RequestBodyInfo::EmojiCreate {
+				name: name.into(),
+				image: encode_image(image)
+			}
+		}, &self.api_base).await
+	}
+
+	/// Removes a custom emoji from a house.
+	pub async fn delete_house_emoji<H>(&self, house: H, emoji_id: u64) -> Result<()>
+			where H: Into<HouseId> {
+		execute_request(&self.http_client, RequestInfo {
+			token: self.token(),
+			path: PathInfo::Emojis {
+				house_id: house.into(),
+				emoji_id: Some(emoji_id)
+			},
+			body: RequestBodyInfo::EmojiDelete {}
+		}, &self.api_base).await?;
+		Ok(())
+	}
+
+	/// Creates a category in a house, to arrange rooms in. Without permission
+	/// to manage rooms, this returns [`Error::Http`] with a 403 status.
+	///
+	/// Names hiven wouldn't accept return [`Error::Validation`] without making
+	/// a request, see [`validate_name`].
+	pub async fn create_category<H, N>(&self, house: H, name: N) -> Result<Category>
+			where H: Into<HouseId>, N: Into<String> {
+		let name = name.into();
+		validate_name(&name)?;
+
+		execute_request_data(&self.http_client, RequestInfo {
+			token: self.token(),
+			path: PathInfo::Categories {
+				house_id: house.into(),
+				category_id: None
+			},
+			body: RequestBodyInfo::CategoryCreate {name}
+		}, &self.api_base).await
+	}
+
+	/// Renames a category in a house. Names are checked like with
+	/// [`Client::create_category`].
+	pub async fn edit_category<H, N>(&self, house: H, category_id: u64, name: N) ->
+			Result<Category>
+				where H: Into<HouseId>, N: Into<String> {
+		let name = name.into();
+		validate_name(&name)?;
+
+		execute_request_data(&self.http_client, RequestInfo {
+			token: self.token(),
+			path: PathInfo::Categories {
+				house_id: house.into(),
+				category_id: Some(category_id)
+			},
+			body: RequestBodyInfo::CategoryEdit {name}
+		}, &self.api_base).await
+	}
+
+	/// Deletes a category from a house. The rooms in it are kept, outside of
+	/// any category.
+	pub async fn delete_category<H>(&self, house: H, category_id: u64) -> Result<()>
+			where H: Into<HouseId> {
+		execute_request(&self.http_client, RequestInfo {
+			token: self.token(),
+			path: PathInfo::Categories {
+				house_id: house.into(),
+				category_id: Some(category_id)
+			},
+			body: RequestBodyInfo::CategoryDelete {}
+		}, &self.api_base).await?;
+		Ok(())
+	}
+
+	/// Sends a message to the room called `room_name` in a house, for bots
+	/// configured with room names rather than ids. The name has to match
+	/// exactly, and if several rooms share it, the first one is used.
+	///
+	/// The house is looked up in the cache with the `cache` feature, and
+	/// fetched otherwise. Returns [`Error::RoomNotFound`] if the house
+	/// has no room by that name.
+	pub async fn send_to_room_named<H, C>(&self, house: H, room_name: &str,
+			content: C) -> Result<Message>
+				where H: Into<HouseId>, C: Into<String> {
+		let house_id = house.into();
+		#[cfg(feature = "cache")]
+		let house = self.cache.house(house_id);
+		#[cfg(not(feature = "cache"))]
+		let house = None;
+		let house = match house {
+			Some(house) => house,
+			None => self.get_house(house_id).await?
+		};
+
+		match house.rooms.iter().find(|room| room.name == room_name) {
+			Some(room) => self.send_message(room.id, content).await,
+			None => Err(Error::RoomNotFound(room_name.to_owned()))
+		}
+	}
+
+	/// Fetches a room by its id. If the room doesn't exist (anymore), this
+	/// returns [`Error::Http`] with a 404 status.
+	pub async fn get_room<R>(&self, room: R) -> Result<Room>
+			where R: Into<RoomId> {
+		self.execute_request_cached(RequestInfo {
+			token: self.token(),
+			path: PathInfo::Room {
+				room_id: room.into()
+			},
+			body: RequestBodyInfo::RoomGet {}
+		}).await
+	}
+
+	/// Moves roles in a house's hierarchy all at once, given pairs of role ids
+	/// and their new positions, and returns every role as it is afterwards.
+	/// Roles that aren't given keep their positions. Without permission to
+	/// manage roles, this returns [`Error::Http`] with a 403 status.
+	pub async fn reorder_roles<H>(&self, house: H, order: Vec<(u64, u32)>) ->
+			Result<Vec<Role>>
+				where H: Into<HouseId> {
+		execute_request_data(&self.http_client, RequestInfo {
+			token: self.token(),
+			path: PathInfo::Roles {
+				house_id: house.into()
+			},
+			body: RequestBodyInfo::RolesReorder(order.into_iter()
+				.map(|(id, position)| RolePosition {id: Snowflake(id), position})
+				.collect())
+		}, &self.api_base).await
+	}
+
+	/// Sets how many seconds members have to wait between messages in a room,
+	/// or turns slowmode off with 0, and returns the updated room. Without
+	/// permission to manage the room, this returns [`Error::Http`] with a 403
+	/// status.
+	///
+	/// Hiven allows up to [`MAX_SLOWMODE`] seconds, anything longer returns
+	/// [`Error::Validation`] without making a request.
+	pub async fn set_slowmode<R>(&self, room: R, seconds: u32) -> Result<Room>
+			where R: Into<RoomId> {
+		if seconds > MAX_SLOWMODE {
+			return Err(Error::Validation(format!(
+				"slowmode of {} seconds is over the maximum of {}", seconds, MAX_SLOWMODE)))
+		}
+
+		execute_request_data(&self.http_client, RequestInfo {
+			token: self.token(),
+			path: PathInfo::Room {
+				room_id: room.into()
+			},
+			body: RequestBodyInfo::RoomEdit {
+				rate_limit_per_user: seconds
+			}
+		}, &self.api_base).await
+	}
+
+	/// Measures how long the api takes to respond to a request, separately
+	/// from gateway latency.
+	///
+	/// There's no dedicated health endpoint, so this requests the root of the
+	/// api. Hiven answering with a client error, such as a 404, still counts as
+	/// a response, while a server error returns [`Error::Http`].
+	pub async fn ping(&self) -> Result<Duration> {
+		let start = Instant::now();
+		let response = self.http_client.get(&self.api_base).send().await?;
+		let elapsed = start.elapsed();
+
+		if response.status().is_server_error() {response.error_for_status()?;}
+		Ok(elapsed)
+	}
+
+	// Gets data that's likely to be polled. With the cache, the ETag of the last
+	// response is sent along, and if hiven says nothing changed, the last
+	// response is used again instead of being sent over.
+	#[cfg(feature = "cache")]
+	async fn execute_request_cached<T>(&self, request: RequestInfo) -> Result<T>
+			where T: DeserializeOwned {
+		let url = format!("{}{}", self.api_base, request.path.path());
+		let cached = self.cache.response(&url);
+		let mut http_request = self.http_client.get(&url)
+			.header("authorization", request.token);
+		if let Some((etag, _)) = &cached {
+			http_request = http_request.header("if-none-match", etag.as_str());
+		}
+
+		let response = http_request.send().await?;
+		let body = match cached {
+			Some((_, body)) if response.status() == StatusCode::NOT_MODIFIED => body,
+			_ => {
+				let response = check_status(response)?;
+				let etag = response.headers().get("etag")
+					.and_then(|etag| etag.to_str().ok()).map(str::to_owned);
+				let body = response.bytes().await?.to_vec();
+				if let Some(etag) = etag {self.cache.insert_response(url, etag, body.clone())}
+				body
+			}
+		};
+
+		let response: ResponseInfo<T> = from_json_slice(&body)?;
+		Ok(response.data)
+	}
+
+	#[cfg(not(feature = "cache"))]
+	async fn execute_request_cached<T>(&self, request: RequestInfo) -> Result<T>
+			where T: DeserializeOwned {
+		execute_request_data(&self.http_client, request, &self.api_base).await
+	}
+
+	/// Makes a request to an api endpoint the crate doesn't have a method for
+	/// yet, such as one hiven just added. `path` is relative to the api's base
+	/// url, like `/users/@me`, and `body` is sent as json.
+	///
+	/// The token is sent like with every other method, and error statuses
+	/// return [`Error::Http`]. Unlike most methods, a request hiven rate limits
+	/// is waited out and tried again like with [`Client::send_many`], returning
+	/// [`Error::RateLimited`] once it's given up on.
+	/// Otherwise the response is returned as is, for reading however the
+	/// endpoint needs.
+	pub async fn raw_request(&self, method: Method, path: &str, body: Option<JSONValue>) ->
+			Result<HTTPResponse> {
+		if !path.starts_with('/') {
+			return Err(Error::Validation(format!("{:?} doesn't start with a /", path)))
+		}
+		let body = body.map(|body| to_json(&body)).transpose()?;
+
+		retry_rate_limited(|| send_request(&self.http_client, method.clone(), path,
+			self.token(), body.clone(), &self.api_base)).await
+	}
+
+	// Gets a url outside the api, like a file on hiven's media server, without
+	// sending the token along.
+	pub(crate) async fn fetch_url(&self, url: &str) -> Result<HTTPResponse> {
+		check_status(self.http_client.get(url).send().await?)
+	}
+
+	/// Fetches the permission overrides set in a room.
+	pub async fn get_room_permissions<R>(&self, room: R) ->
+			Result<Vec<PermissionOverride>>
+				where R: Into<RoomId> {
+		self.execute_request_cached(RequestInfo {
+			token: self.token(),
+			path: PathInfo::RoomPermissions {
+				room_id: room.into(),
+				target_id: None
+			},
+			body: RequestBodyInfo::RoomPermissionsGet {}
+		}).await
+	}
+
+	/// Sets the permission override for a role or member in a room, replacing
+	/// any override it already had. Returns the override as hiven saved it.
+	pub async fn set_room_permission_override<R>(&self, room: R,
+			permission_override: PermissionOverride) -> Result<PermissionOverride>
+				where R: Into<RoomId> {
+		execute_request_data(&self.http_client, RequestInfo {
+			token: self.token(),
+			path: PathInfo::RoomPermissions {
+				room_id: room.into(),
+				target_id: Some(permission_override.target_id)
+			},
+			body: RequestBodyInfo::PermissionOverrideSet {
+				target_type: permission_override.target_type,
+				allow: permission_override.allow,
+				deny: permission_override.deny
+			}
+		}, &self.api_base).await
+	}
+
+	pub async fn trigger_typing<R>(&self, room: R) -> Result<()>
+			where R: Into<RoomId> {
+		execute_request(&self.http_client, RequestInfo {
+			token: self.token(),
+			path: PathInfo::TypingTrigger {
+				channel_id: room.into()
+			},
+			body: RequestBodyInfo::TypingTrigger {}
+		}, &self.api_base).await?;
+		Ok(())
+	}
+
+	/// Shows the client's user as typing in a room until the returned guard is
+	/// dropped, for commands that take a while. Hiven only shows a typing
+	/// indicator for [`TYPING_TIMEOUT`], so it's sent again a couple of seconds
+	/// before it runs out.
+	///
+	/// Indicators that fail to send are skipped, the next one is tried anyway.
+	/// This has to be called from within a tokio runtime, as the indicators are
+	/// sent from a task.
+	pub fn keep_typing<R>(&self, room: R) -> TypingGuard
+			where R: Into<RoomId> {
+		self.keep_typing_every(room.into(), TYPING_INTERVAL)
+	}
+
+	fn keep_typing_every(&self, channel_id: RoomId, every: Duration) -> TypingGuard {
+		let (stop, mut stopped) = oneshot_channel::<()>();
+		let (http_client, api_base) = (self.http_client.clone(), self.api_base.clone());
+		let (token, token_type) = (self.token.clone(), self.token_type);
+
+		tokio::spawn(async move {
+			loop {
+				let token = token_type.authorization(&token.read().unwrap());
+				let _ = execute_request(&http_client, RequestInfo {
+					token,
+					path: PathInfo::TypingTrigger {channel_id},
+					body: RequestBodyInfo::TypingTrigger {}
+				}, &api_base).await;
+
+				// Also ends if the guard is dropped mid request.
+				select! {
+					() = delay_for(every) => (),
+					_ = &mut stopped => break
+				}
+			}
+		});
+
+		TypingGuard {_stop: stop}
+	}
+}
+
+/// Keeps a typing indicator up until dropped, returned by
+/// [`Client::keep_typing`].
+#[derive(Debug)]
+pub struct TypingGuard {
+	// Dropping it ends the task sending the indicators.
+	_stop: OneshotSender<()>
+}
+
+/// A builder for configuring a [`Client`] beyond what [`Client::new`] and
+/// [`Client::new_at`] allow.
+///
+/// ```rust
+/// use hiven_rs::client::ClientBuilder;
+///
+/// # fn example() -> Result<(), hiven_rs::client::Error> {
+/// let client = ClientBuilder::new("token")
+///     .domains("api.hiven.io", "swarm-dev.hiven.io")
+///     .try_build()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ClientBuilder {
+	token: String,
+	token_type: TokenType,
+	domains: (String, String),
+	gateway_scheme: &'static str,
+	user_agent: String,
+	api_version: u32,
+	event_concurrency: usize,
+	connect_timeout: Duration,
+	gateway: GatewayConfig,
+	#[cfg(feature = "record-frames")]
+	frame_record: Option<PathBuf>
+}
+
+impl ClientBuilder {
+	/// Creates a new builder with an authentication token. Uses the official
+	/// hiven.io servers unless told otherwise.
+	pub fn new<T>(token: T) -> Self
+			where T: Into<String> {
+		Self {
+			token: token.into(),
+			token_type: TokenType::default(),
+			domains: ("api.hiven.io".to_owned(), "swarm-dev.hiven.io".to_owned()),
+			gateway_scheme: "wss",
+			user_agent: USER_AGENT.to_owned(),
+			api_version: 1,
+			event_concurrency: 1,
+			connect_timeout: Duration::from_secs(10),
+			gateway: GatewayConfig::default(),
+			#[cfg(feature = "record-frames")]
+			frame_record: None
+		}
+	}
+
+	/// Sets what kind of token the client was given, which decides how it's
+	/// sent. Defaults to [`TokenType::Token`].
+	pub fn token_type(mut self, token_type: TokenType) -> Self {
+		self.token_type = token_type;
+		self
+	}
+
+	/// Sets the base domain for the api and gateway.
+	pub fn domains<A, G>(mut self, api_base: A, gateway_base: G) -> Self
+			where A: Into<String>, G: Into<String> {
+		self.domains = (api_base.into(), gateway_base.into());
+		self
+	}
+
+	/// Sets the `User-Agent` header sent with api requests. Defaults to
+	/// [`USER_AGENT`].
+	pub fn user_agent<U>(mut self, user_agent: U) -> Self
+			where U: Into<String> {
+		self.user_agent = user_agent.into();
+		self
+	}
+
+	/// Sets the version of the api requests are made to, as in
+	/// `https://api.hiven.io/v1`. Defaults to 1.
+	pub fn api_version(mut self, version: u32) -> Self {
+		self.api_version = version;
+		self
+	}
+
+	/// Sets how many event handlers may run at once. Defaults to 1, where each
+	/// handler has to finish before the next event is handled.
+	///
+	/// With a higher limit, a slow handler (one waiting on a request, say) no
+	/// longer holds up the events after it. The tradeoff is ordering: handlers
+	/// still start in the order events arrive, but may finish in any order, so
+	/// a handler can't rely on the previous event having been fully handled.
+	///
+	/// Handlers run concurrently within the gateway's task rather than being
+	/// spawned onto the runtime, since they borrow the client. A limit of 0 is
+	/// treated as 1.
+	///
+	/// While the limit is reached, the gateway stops reading until a handler
+	/// finishes, so no events are skipped, but hiven may see the connection
+	/// as slow. Streams from [`Client::events`] aren't held up by this, and
+	/// skip events themselves if they fall behind.
+	pub fn event_concurrency(mut self, limit: usize) -> Self {
+		self.event_concurrency = limit.max(1);
+		self
+	}
+
+	/// Sets how long connecting may take, for both the gateway and api
+	/// requests, before giving up with [`Error::ConnectTimeout`]. Defaults to 10
+	/// seconds.
+	///
+	/// Without it, connecting to a host that never answers would only fail
+	/// once the OS gives up, which can take minutes. For the gateway this
+	/// covers the websocket handshake too.
+	pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+		self.connect_timeout = connect_timeout;
+		self
+	}
+
+	/// Sets how the gateway connects, stays alive and reconnects, see
+	/// [`GatewayConfig`]. Setting one part of it afterwards, like
+	/// [`reconnect`](ClientBuilder::reconnect), only changes that part.
+	pub fn gateway_config(mut self, config: GatewayConfig) -> Self {
+		self.gateway = config;
+		self
+	}
+
+	/// Sets the gateway's [handshake timeout](GatewayConfig::handshake_timeout).
+	pub fn handshake_timeout(mut self, handshake_timeout: Duration) -> Self {
+		self.gateway = self.gateway.handshake_timeout(handshake_timeout);
+		self
+	}
+
+	/// Sets the gateway's [keepalive](GatewayConfig::keepalive).
+	pub fn keepalive(mut self, period: Option<Duration>) -> Self {
+		self.gateway = self.gateway.keepalive(period);
+		self
+	}
+
+	/// Sets whether the gateway [reconnects](GatewayConfig::reconnect).
+	pub fn reconnect(mut self, reconnect: bool) -> Self {
+		self.gateway = self.gateway.reconnect(reconnect);
+		self
+	}
+
+	/// Sets the gateway's
+	/// [most reconnect attempts](GatewayConfig::max_reconnect_attempts).
+	pub fn max_reconnect_attempts(mut self, max: Option<u32>) -> Self {
+		self.gateway = self.gateway.max_reconnect_attempts(max);
+		self
+	}
+
+	/// Sets whether the gateway's reconnects have
+	/// [jitter](GatewayConfig::reconnect_jitter).
+	pub fn reconnect_jitter(mut self, jitter: bool) -> Self {
+		self.gateway = self.gateway.reconnect_jitter(jitter);
+		self
+	}
+
+	/// Connects to the gateway without TLS, as the mock gateway doesn't speak
+	/// it.
+	#[cfg(any(test, feature = "mock-gateway"))]
+	pub(crate) fn insecure_gateway(mut self) -> Self {
+		self.gateway_scheme = "ws";
+		self
+	}
+
+	/// Appends every raw text frame received from the gateway to the file at
+	/// `path`, one frame per line. The file is created if it doesn't exist.
+	///
+	/// This is meant for debugging the protocol, for instance when reporting an
+	/// event that can't be parsed yet. Only available with the `record-frames`
+	/// feature.
+	#[cfg(feature = "record-frames")]
+	pub fn record_frames<P>(mut self, path: P) -> Self
+			where P: Into<PathBuf> {
+		self.frame_record = Some(path.into());
+		self
+	}
+
+	/// Builds the client. Panics if either domain isn't valid, see
+	/// [`try_build`](ClientBuilder::try_build).
+	#[deprecated(since = "0.2.0",
+		note = "panics on an invalid domain, use try_build instead")]
+	pub fn build(self) -> Client {
+		match self.try_build() {
+			Ok(client) => client,
+			Err(err) => panic!("couldn't build the client: {:?}", err)
+		}
+	}
+
+	/// Builds the client, or returns [`Error::InvalidDomain`] if either domain
+	/// isn't a valid host.
+	///
+	/// Domains are normalized first, so trailing slashes are dropped, and a
+	/// scheme decides whether TLS is used: `http://localhost:8080/` is taken as
+	/// `localhost:8080` without it, while `https://`, `wss://` or no scheme at
+	/// all use it. `http://` and `ws://` mean the same for either domain, as do
+	/// `https://` and `wss://`. Other schemes, or anything besides a host and
+	/// port, such as a path, are an error.
+	pub fn try_build(self) -> Result<Client> {
+		let ((api_tls, api_host), (gateway_tls, gateway_host)) =
+			(normalize_domain(self.domains.0)?, normalize_domain(self.domains.1)?);
+		let api_scheme = api_tls.map_or("https", api_scheme);
+		let domains = (api_host, gateway_host);
+
+		Ok(Client {
+			token: Arc::new(RwLock::new(self.token)),
+			token_type: self.token_type,
+			relogins: broadcast_channel(1).0,
+			events: broadcast_channel(EVENT_BUFFER).0,
+			api_base: api_url(api_scheme, &domains.0, self.api_version),
+			domains,
+			api_version: self.api_version,
+			api_scheme,
+			gateway_scheme: gateway_tls.map_or(self.gateway_scheme, gateway_scheme),
+			// Building only fails if the TLS backend can't be initialized, which
+			// HTTPClient::new would panic on too.
+			http_client: HTTPClient::builder().user_agent(self.user_agent)
+				.connect_timeout(self.connect_timeout).build()
+				.expect("the TLS backend couldn't be initialized"),
+			event_concurrency: self.event_concurrency,
+			connect_timeout: self.connect_timeout,
+			gateway: self.gateway,
+			connected: AtomicBool::new(false),
+			user_id: AtomicU64::new(0),
+			stats: GatewayStats::default(),
+			outgoing: Mutex::new(VecDeque::new()),
+			outgoing_ready: Notify::new(),
+			#[cfg(feature = "cache")]
+			cache: Cache::new(),
+			#[cfg(feature = "record-frames")]
+			frame_record: self.frame_record
+		})
+	}
+}
+
+/// What kind of token a client authenticates with, set with
+/// [`ClientBuilder::token_type`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum TokenType {
+	/// A token for a user or bot account, sent as it is.
+	#[default]
+	Token,
+	/// An OAuth2 access token, for apps acting on behalf of a user who
+	/// authorized them. Sent as `Bearer <token>`.
+	Bearer
+}
+
+impl TokenType {
+	// The token with this type's scheme, as hiven expects it.
+	fn authorization(self, token: &str) -> String {
+		match self {
+			Self::Token => token.to_owned(),
+			Self::Bearer => format!("Bearer {}", token)
+		}
+	}
+}
+
+/// How the gateway connects, stays alive and reconnects, for
+/// [`ClientBuilder::gateway_config`]. The default is what a client gets
+/// without one: a 10 second handshake timeout, no keepalive, and no
+/// reconnecting.
+///
+/// ```rust
+/// use hiven_rs::client::{ClientBuilder, GatewayConfig};
+///
+/// let policy = GatewayConfig::default().reconnect(true).max_reconnect_attempts(Some(5));
+/// let client = ClientBuilder::new("token").gateway_config(policy.clone()).try_build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct GatewayConfig {
+	handshake_timeout: Duration,
+	keepalive: Option<Duration>,
+	reconnect: bool,
+	max_reconnect_attempts: Option<u32>,
+	reconnect_jitter: bool
+}
+
+impl Default for GatewayConfig {
+	fn default() -> Self {
+		Self {
+			handshake_timeout: Duration::from_secs(10),
+			keepalive: None,
+			reconnect: false,
+			max_reconnect_attempts: None,
+			reconnect_jitter: true
+		}
+	}
+}
+
+impl GatewayConfig {
+	/// Sets how long hiven may take to log the client in once the gateway is
+	/// connected, from saying hello to sending the initial state, before giving
+	/// up with [`Error::HandshakeTimeout`]. Defaults to 10 seconds.
+	pub fn handshake_timeout(mut self, handshake_timeout: Duration) -> Self {
+		self.handshake_timeout = handshake_timeout;
+		self
+	}
+
+	/// Sends a websocket ping this often, on top of hiven's heart beats, or
+	/// doesn't with `None`. Off by default.
+	///
+	/// Some proxies and load balancers drop websockets that look idle, even
+	/// though hiven's heart beats keep the session itself alive. A ping more
+	/// often than the proxy's idle timeout keeps them happy.
+	pub fn keepalive(mut self, period: Option<Duration>) -> Self {
+		self.keepalive = period;
+		self
+	}
+
+	/// Sets whether the gateway reconnects when the connection is lost, rather
+	/// than returning the error. Off by default.
+	///
+	/// The first reconnect happens right away, and failed attempts after it
+	/// back off exponentially, up to a minute between attempts, with
+	/// [jitter](GatewayConfig::reconnect_jitter). Shutting the gateway down
+	/// stops it from reconnecting.
+	pub fn reconnect(mut self, reconnect: bool) -> Self {
+		self.reconnect = reconnect;
+		self
+	}
+
+	/// Sets how many times in a row the gateway tries to reconnect before
+	/// giving up with [`Error::ReconnectExhausted`], or `None` to keep trying
+	/// forever, which is the default. Logging in resets the count.
+	///
+	/// Giving up suits deployments where a supervisor restarts the process
+	/// anyway. Only matters if the client is set to [reconnect].
+	///
+	/// [reconnect]: GatewayConfig::reconnect
+	pub fn max_reconnect_attempts(mut self, max: Option<u32>) -> Self {
+		self.max_reconnect_attempts = max;
+		self
+	}
+
+	/// Sets whether the wait between reconnect attempts is randomized, to
+	/// anywhere between nothing and the backoff. On by default, so bots that
+	/// lost their connections at the same time, say in an outage, don't all
+	/// retry at the same moments.
+	pub fn reconnect_jitter(mut self, jitter: bool) -> Self {
+		self.reconnect_jitter = jitter;
+		self
+	}
+}
+
+// Whether a url is well formed and points at the web, rather than being, say,
+// a file path.
+fn is_web_url(url: &str) -> bool {
+	Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https")
+		&& url.host().is_some())
+}
+
+fn api_url(scheme: &str, domain: &str, version: u32) -> String {
+	format!("{}://{}/v{}", scheme, domain, version)
+}
+
+fn api_scheme(tls: bool) -> &'static str {
+	if tls {"https"} else {"http"}
+}
+
+fn gateway_scheme(tls: bool) -> &'static str {
+	if tls {"wss"} else {"ws"}
+}
+
+// Strips the scheme and trailing slashes a domain might have been configured
+// with, and checks that what's left is just a host, and maybe a port. Along
+// with it is whether the scheme asked for TLS, if there was one.
+fn normalize_domain(domain: String) -> Result<(Option<bool>, String)> {
+	let (tls, host) = match domain.find("://") {
+		Some(index) => match &domain[..index] {
+			"https" | "wss" => (Some(true), &domain[index + 3..]),
+			"http" | "ws" => (Some(false), &domain[index + 3..]),
+			_ => return Err(Error::InvalidDomain(domain))
+		},
+		None => (None, domain.as_str())
+	};
+	let host = host.trim_end_matches('/');
+
+	let valid = |character: char| character.is_ascii_alphanumeric()
+		|| "-.:[]".contains(character);
+	if host.is_empty() || !host.chars().all(valid) {
+		return Err(Error::InvalidDomain(domain))
+	}
+
+	Ok((tls, host.to_owned()))
+}
+
+impl Client {
+	pub fn start_gateway_later<E>(self: Arc<Self>, event_handler: E) ->
+			JoinHandle<()>
+				where E: EventHandler + 'static {
+		spawn(move || {
+			let gate_keeper = GateKeeper::new(&self, event_handler);
+			let mut runtime = tokio::runtime::Runtime::new().unwrap();
+			runtime.block_on(async {
+				match gate_keeper.start_gateway().await {
+					GatewayExit::Shutdown => (),
+					exit => panic!("the gateway stopped: {:?}", exit)
+				}
+			});
+		})
+	}
+}
+
+/// A message to be sent, with optional settings.
+///
+/// ```rust
+/// use hiven_rs::{client::MessageBuilder, data::MessageFlags};
+///
+/// let message = MessageBuilder::new("Deploy finished.")
+///     .flags(MessageFlags::SILENT);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MessageBuilder {
+	content: String,
+	flags: MessageFlags,
+	nonce: Option<String>,
+	sticker_id: Option<u64>,
+	attachment_urls: Vec<String>,
+	embed: Option<Embed>
+}
+
+impl MessageBuilder {
+	/// Creates a message with the given content, no flags, and a random nonce.
+	pub fn new<C>(content: C) -> Self
+			where C: Into<String> {
+		Self {
+			content: content.into(),
+			flags: MessageFlags::empty(),
+			nonce: Some(random::<u64>().to_string()),
+			sticker_id: None,
+			attachment_urls: Vec::new(),
+			embed: None
+		}
+	}
+
+	/// Attaches a sticker to the message. The content can be left empty to
+	/// send just the sticker.
+	pub fn sticker(mut self, sticker_id: u64) -> Self {
+		self.sticker_id = Some(sticker_id);
+		self
+	}
+
+	/// Attaches a file hosted elsewhere, by its url, for hiven to link to
+	/// instead of the file being uploaded. Can be called more than once.
+	///
+	/// Urls have to be http or https, otherwise sending the message returns
+	/// [`Error::Validation`] without making a request.
+	pub fn attachment_url<U>(mut self, url: U) -> Self
+			where U: Into<String> {
+		self.attachment_urls.push(url.into());
+		self
+	}
+
+	/// Sends the message with an embed. The content can be left empty to send
+	/// just the embed.
+	pub fn embed(mut self, embed: Embed) -> Self {
+		self.embed = Some(embed);
+		self
+	}
+
+	/// Sets the nonce sent with the message, or sends it without one. Hiven
+	/// echoes the nonce back on the sent [`Message`].
+	///
+	/// Sending the same builder again, say after a request timed out, reuses
+	/// its nonce, so a message that made it through the first time can be
+	/// told apart from a duplicate.
+	pub fn nonce(mut self, nonce: Option<String>) -> Self {
+		self.nonce = nonce;
+		self
+	}
+
+	/// Sets the flags the message is sent with.
+	pub fn flags(mut self, flags: MessageFlags) -> Self {
+		self.flags = flags;
+		self
+	}
+}
+
+/// Changes to make to an existing message. Anything that isn't set is left as
+/// it is.
+///
+/// ```rust
+/// use hiven_rs::{client::MessageEditBuilder, data::Embed};
+///
+/// let edit = MessageEditBuilder::new().embed(Embed {
+///     title: Some("Status".to_owned()),
+///     description: Some("All systems operational.".to_owned()),
+///     ..Embed::default()
+/// });
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct MessageEditBuilder {
+	content: Option<String>,
+	embed: Option<Embed>
+}
+
+impl MessageEditBuilder {
+	/// Creates an edit that doesn't change anything yet.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Replaces the content of the message.
+	pub fn content<C>(mut self, content: C) -> Self
+			where C: Into<String> {
+		self.content = Some(content.into());
+		self
+	}
+
+	/// Replaces the embed of the message.
+	pub fn embed(mut self, embed: Embed) -> Self {
+		self.embed = Some(embed);
+		self
+	}
+}
+
+/// Options for banning a member. Options that aren't set are left out of the
+/// request, so hiven's defaults apply.
+///
+/// ```rust
+/// use hiven_rs::client::BanBuilder;
+///
+/// let ban = BanBuilder::new().reason("Spam").delete_message_days(1);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct BanBuilder {
+	reason: Option<String>,
+	delete_message_days: Option<u8>
+}
+
+impl BanBuilder {
+	/// Creates a ban without a reason, that doesn't delete any messages.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Sets the reason for the ban, shown in the house's audit log.
+	pub fn reason<R>(mut self, reason: R) -> Self
+			where R: Into<String> {
+		self.reason = Some(reason.into());
+		self
+	}
+
+	/// Deletes the member's messages from the last `days` days, so a ban can
+	/// double as a clean up.
+	pub fn delete_message_days(mut self, days: u8) -> Self {
+		self.delete_message_days = Some(days);
+		self
+	}
+}
+
+/// Which audit log entries to get. Options that aren't set don't filter
+/// anything.
+///
+/// ```rust
+/// use hiven_rs::{client::AuditFilter, data::AuditAction};
+///
+/// let bans = AuditFilter::new().action(AuditAction::MemberBan).actor(1);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct AuditFilter {
+	action_type: Option<AuditAction>,
+	actor_id: Option<UserId>,
+	before: Option<u64>
+}
+
+impl AuditFilter {
+	/// Creates a filter for the newest entries, of any kind and by anyone.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Only gets entries for one kind of action.
+	pub fn action(mut self, action_type: AuditAction) -> Self {
+		self.action_type = Some(action_type);
+		self
+	}
+
+	/// Only gets entries for things a certain member did.
+	pub fn actor<U>(mut self, actor_id: U) -> Self
+			where U: Into<UserId> {
+		self.actor_id = Some(actor_id.into());
+		self
+	}
+
+	/// Only gets entries older than the one with this id, to page through the
+	/// log.
+	pub fn before(mut self, entry_id: u64) -> Self {
+		self.before = Some(entry_id);
+		self
+	}
+}
+
+async fn execute_request(client: &HTTPClient, request: RequestInfo,
+		api_base: &str) -> Result<HTTPResponse> {
+	let method = request.body.method();
+	let body = if method != Method::GET {Some(to_json(&request.body)?)} else {None};
+	send_request(client, method, &request.path.path(), request.token, body, api_base).await
+}
+
+// Sends a request to an api path with the token, and a json body if there is
+// one.
+async fn send_request(client: &HTTPClient, method: Method, path: &str, token: String,
+		body: Option<String>, api_base: &str) -> Result<HTTPResponse> {
+	let http_request = client.request(method, &format!("{}{}", api_base, path))
+		.header("authorization", token);
+
+	let http_request = match body {
+		Some(body) => http_request.header("content-type", "application/json").body(body),
+		None => http_request
+	};
+
+	check_status(http_request.send().await?)
+}
+
+// Turns an error status into an error. When rate limited, how long hiven asked
+// to wait is kept.
+fn check_status(response: HTTPResponse) -> Result<HTTPResponse> {
+	let retry_after = response.headers().get("retry-after")
+		.and_then(|seconds| seconds.to_str().ok()?.parse().ok())
+		.map(Duration::from_secs);
+
+	match response.error_for_status() {
+		Err(err) if err.status() == Some(StatusCode::TOO_MANY_REQUESTS) =>
+			Err(Error::RateLimited(retry_after, err)),
+		response => Ok(response?)
+	}
+}
+
+/// Executes a request, returning the data hiven responded with.
+async fn execute_request_data<T>(client: &HTTPClient, request: RequestInfo,
+		api_base: &str) -> Result<T>
+			where T: DeserializeOwned {
+	let response = execute_request(client, request, api_base).await?;
+	let response: ResponseInfo<T> = from_json_slice(&response.bytes().await?)?;
+	Ok(response.data)
+}
+
+pub struct GateKeeper<'c, E>
+		where E: EventHandler {
+	pub client: &'c Client,
+	pub event_handler: E,
+	shutdown: ShutdownHandle,
+	// Set when a connection is closed so it can log in again straight away.
+	relogin: AtomicBool
+}
+
+impl<'c, E> GateKeeper<'c, E>
+		where E: EventHandler {
+	pub fn new(client: &'c Client, event_handler: E) -> Self {
+		Self {
+			client,
+			event_handler,
+			shutdown: ShutdownHandle::new(),
+			relogin: AtomicBool::new(false)
+		}
+	}
+
+	/// Gets a handle that can close this gate keeper's connection gracefully,
+	/// from anywhere.
+	pub fn shutdown_handle(&self) -> ShutdownHandle {
+		self.shutdown.clone()
+	}
+
+	/// Runs the gateway until it's shut down, until hiven rejects the token, or
+	/// until the connection is lost and the client isn't set to [reconnect].
+	///
+	/// Dropping the returned future rather than shutting down through a
+	/// [`ShutdownHandle`] skips the clean close. The socket is closed in the
+	/// background, and event handlers that are still running are dropped.
+	///
+	/// [reconnect]: GatewayConfig::reconnect
+	pub async fn start_gateway(&self) -> GatewayExit {
+		let _running = self.shutdown.running();
+		let stats = &self.client.stats;
+		let mut attempt = 0;
+
+		loop {
+			let logins = stats.logins.load(Ordering::Acquire);
+			stats.connection_attempts.fetch_add(1, Ordering::AcqRel);
+
+			let result = self.connect().await;
+			if let Err(Error::SocketClose(close_frame)) = &result {
+				self.event_handler.on_disconnect(self.client, close_frame.clone()).await;
+			}
+
+			let err = match result {
+				Ok(()) if self.relogin.swap(false, Ordering::AcqRel) => {
+					attempt = 0;
+					continue
+				},
+				Ok(()) => return GatewayExit::Shutdown,
+				// Hiven closes the connection with a code of its own instead of
+				// sending the initial state when it doesn't like the token, so
+				// trying again won't help. Any other close is just a lost
+				// connection.
+				Err(Error::SocketClose(Some(close_frame)))
+					if u16::from(close_frame.code) == AUTH_FAILED_CLOSE_CODE
+						&& stats.logins.load(Ordering::Acquire) == logins =>
+							return GatewayExit::AuthFailed,
+				Err(err) if !self.client.gateway.reconnect => return GatewayExit::Fatal(err),
+				Err(err) => err
+			};
+			*stats.last_disconnect.lock().unwrap() = Some(format!("{:?}", err));
+
+			// Start backing off from scratch if we made it as far as logging in.
+			if stats.logins.load(Ordering::Acquire) != logins {attempt = 0}
+			if let Some(max) = self.client.gateway.max_reconnect_attempts {
+				if attempt >= max {
+					return GatewayExit::Fatal(Error::ReconnectExhausted(Box::new(err)))
+				}
+			}
+			let mut delay = reconnect_delay(attempt);
+			if self.client.gateway.reconnect_jitter {delay = jitter(delay)}
+			select! {
+				() = delay_for(delay) => attempt += 1,
+				() = self.shutdown.notify.notified() => return GatewayExit::Shutdown
+			}
+		}
+	}
+
+	// Makes a single connection to the gateway, and runs it until it ends.
+	async fn connect(&self) -> Result<()> {
+		let (outgoing_send, outgoing_receive) = channel(5);
+		let (incoming_send, incoming_receive) = channel(5);
+
+		let manager = async {
+			let result = self.manage_gateway(incoming_send, outgoing_receive).await;
+			self.client.connected.store(false, Ordering::Release);
+			*self.client.stats.connected_at.lock().unwrap() = None;
+			result
+		};
+
+		match join!(
+			manager,
+			self.listen_gateway(incoming_receive, outgoing_send)
+		) {
+			(Ok(()), Ok(())) => Ok(()),
+			(Err(err), Ok(())) => Err(err),
+			(Ok(()), Err(err)) => Err(err),
+			(Err(_), Err(err)) => Err(err)
+		}
+	}
+
+	// Every frame is sent to the listener, and events are broadcast to the
+	// client's streams and collectors too.
+	async fn manage_gateway(&self, mut sender: Sender<Frame>,
+			mut receiver: Receiver<Option<Frame>>) -> Result<()> {
+		let url = format!("{}://{}/socket", self.client.gateway_scheme,
+			self.client.domains.1);
+		let mut relogins = self.client.relogins.subscribe();
+		let mut logged_in = false;
+		let socket = timeout(self.client.connect_timeout, websocket_async(url)).await
+			.map_err(|_| Error::ConnectTimeout)??.0;
+		let mut socket = CloseOnDrop(Some(socket));
+		// Runs from here until the initial state, not just until hello.
+		let mut handshake = delay_for(self.client.gateway.handshake_timeout);
+		let mut keepalive = self.client.gateway.keepalive
+			.map(|period| interval_at(TokioInstant::now() + period, period));
+		#[cfg(feature = "record-frames")]
+		let mut frame_record = match &self.client.frame_record {
+			Some(path) => Some(OpenOptions::new().create(true).append(true)
+				.open(path).await?),
+			None => None
+		};
+
+		loop {
+			let incoming_frame = socket.next();
+			let outgoing_frame = receiver.next();
+
+			select! {
+				// The socket ending without a close frame is a close too.
+				frame = incoming_frame => match binary_as_text(frame
+						.ok_or_else(|| Error::socket_close(None))??) {
+					WebsocketMessage::Text(frame) => {
+						#[cfg(feature = "record-frames")]
+						if let Some(file) = &mut frame_record {
+							// Newlines in JSON can only be whitespace, keep one frame per
+							// line.
+							let line = format!("{}\n", frame.replace('\n', " "));
+							file.write_all(line.as_bytes()).await?;
+						}
+
+						self.event_handler.on_raw(self.client, &frame);
+
+						// Tungstenite has already validated and allocated the text, so
+						// parsing it in place costs the same as from_slice would, and
+						// to_string below allocates exactly once too.
+						if let Ok(frame) = from_json::<Frame>(&frame) {
+							if let Frame::Event(OpCodeEvent::InitState(data)) = &frame {
+								self.client.user_id.store(data.user.id.into(), Ordering::Release);
+								let stats = &self.client.stats;
+								*stats.connected_at.lock().unwrap() = Some(Instant::now());
+								stats.logins.fetch_add(1, Ordering::AcqRel);
+								self.client.connected.store(true, Ordering::Release);
+								// Send whatever was queued while we were away.
+								logged_in = true;
+								self.client.outgoing_ready.notify();
+							}
+
+							if let Frame::Event(event) = &frame {
+								#[cfg(feature = "cache")]
+								self.client.cache.update(event);
+								// Nobody listening is fine.
+								let _ = self.client.events.send(event.clone());
+							}
+							// Waits while the event handler is busy, rather than dropping
+							// events.
+							sender.send(frame).await?
+						}
+					},
+					// Tungstenite queues a pong for us, flush so it's sent right away.
+					WebsocketMessage::Ping(_) => socket.flush().await?,
+					WebsocketMessage::Pong(_) => (),
+					// Binary frames that aren't UTF-8, like compressed ones, are
+					// ignored for now.
+					WebsocketMessage::Binary(_) => (),
+					WebsocketMessage::Close(close_data) => return Err(Error::socket_close(close_data))
+				},
+				frame = outgoing_frame => match frame {
+					Some(Some(frame)) => {
+						let heart_beat = matches!(frame, Frame::HeartBeat);
+						match socket.send(WebsocketMessage::Text(to_json(&frame)?)).await {
+							Ok(()) => (),
+							Err(err) if heart_beat => return Err(Error::HeartbeatFailed(err)),
+							Err(err) => return Err(err.into())
+						}
+					},
+					Some(None) => (),
+					// The listener hung up, exit gracefully.
+					None => return Ok(())
+				},
+				() = self.shutdown.notify.notified() => {
+					socket.close(None).await?;
+					return Ok(())
+				},
+				() = tick(&mut keepalive) =>
+					socket.send(WebsocketMessage::Ping(Vec::new())).await?,
+				() = &mut handshake, if !logged_in => return Err(Error::HandshakeTimeout),
+				() = self.client.outgoing_ready.notified(), if logged_in => {
+					for frame in self.client.take_outgoing() {
+						socket.send(WebsocketMessage::Text(to_json(&frame)?)).await?;
+					}
+				},
+				_ = relogins.recv() => {
+					self.relogin.store(true, Ordering::Release);
+					socket.close(None).await?;
+					return Ok(())
+				}
+			}
+		}
+	}
+
+	async fn listen_gateway(&self, mut receiver: Receiver<Frame>,
+			mut sender: Sender<Option<Frame>>) -> Result<()> {
+		let notify = Notify::new();
+		// Hiven may send another hello to change the interval mid session.
+		let interval = AtomicU64::new(0);
+		let interval_changed = Notify::new();
+
+		// The manager gives up if this takes too long, see handshake_timeout.
+		let heart_beat = match receiver.next().await {
+			// We got what we needed.
+			Some(Frame::Hello(OpCodeHello {heart_beat})) => {
+				interval.store(heart_beat.into(), Ordering::Release);
+				let bag = (sender.clone(), &notify, &interval, &interval_changed);
+
+				// Set heart_beat to our heart beat future.
+				async move {
+					let (mut sender, notify, interval, interval_changed) = bag;
+
+					loop {
+						let duration = Duration::from_millis(interval.load(Ordering::Acquire));
+						select! {
+							() = notify.notified() => return Result::Ok(()),
+							// Start waiting again, with the new interval.
+							() = interval_changed.notified() => continue,
+							() = delay_for(duration) => ()
+						}
+						// The manager hung up, it reports why itself. Heart beats that
+						// can't be written to the socket are reported as HeartbeatFailed.
+						if sender.send(Some(Frame::HeartBeat)).await.is_err()
+							{return Result::Ok(())}
+					}
+				}
+			},
+			// Expectation failed.
+			Some(frame) => return Err(Error::expectation_failed(
+				"Frame::Hello(...)", frame)),
+			// The channel died, exit gracefully.
+			None => return Ok(())
+		};
+
+		let login_frame = Frame::Login(OpCodeLogin {
+			token: self.client.token()
+		});
+		sender.send(Some(login_frame)).await?;
+
+		let listener = async {
+			let limit = self.client.event_concurrency;
+			let mut handlers = FuturesUnordered::new();
+
+			let result = loop {
+				select! {
+					// Nothing is read while the limit is reached, so the manager waits
+					// for handlers to catch up.
+					frame = receiver.next(), if handlers.len() < limit => match frame {
+						Some(Frame::Event(event)) => handlers.push(self.dispatch(event)),
+						// Hiven asked for a heart beat, answer right away on top of
+						// the timed ones. If those already fill the queue, or the
+						// manager hung up, there's no need.
+						Some(Frame::HeartBeat) =>
+							{let _ = sender.try_send(Some(Frame::HeartBeat));},
+						Some(Frame::Hello(OpCodeHello {heart_beat})) => {
+							interval.store(heart_beat.into(), Ordering::Release);
+							interval_changed.notify();
+						},
+						// The channel died, exit gracefully. Events the manager sent
+						// before hanging up have all been read by now.
+						None => break Result::Ok(()),
+						// Only clients send anything else, like a login, so a server that
+						// does is reported and otherwise ignored.
+						Some(frame) => self.event_handler.on_error(self.client,
+							&Error::expectation_failed("a frame hiven sends", frame))
+					},
+					Some(()) = handlers.next(), if !handlers.is_empty() => ()
+				}
+			};
+
+			// Let handlers that are still running finish.
+			while handlers.next().await.is_some() {}
+			notify.notify();
+			result
+		};
+
+		match join!(heart_beat, listener) {
+			(Ok(()), Ok(())) => Ok(()),
+			(Err(err), Ok(())) => Err(err),
+			(Ok(()), Err(err)) => Err(err),
+			(Err(_), Err(err)) => Err(err)
+		}
+	}
+
+	fn dispatch(&self, event: OpCodeEvent) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		match event {
+			OpCodeEvent::InitState(data) =>
+				self.event_handler.on_connect(self.client, data),
+			OpCodeEvent::HouseJoin(data) =>
+				self.event_handler.on_house_join(self.client, data),
+			OpCodeEvent::HouseLeave(data) =>
+				self.event_handler.on_house_leave(self.client, data),
+			OpCodeEvent::HouseDelete(data) =>
+				self.event_handler.on_house_delete(self.client, data),
+			OpCodeEvent::TypingStart(data) =>
+				self.event_handler.on_typing(self.client, data),
+			OpCodeEvent::MessageCreate(data) =>
+				self.event_handler.on_message(self.client, data),
+			OpCodeEvent::MessagePinned(data) =>
+				self.event_handler.on_message_pin(self.client, data),
+			OpCodeEvent::MessageUnpinned(data) =>
+				self.event_handler.on_message_unpin(self.client, data),
+			OpCodeEvent::PresenceUpdate(data) =>
+				self.event_handler.on_presence_update(self.client, data)
+		}
+	}
+}
+
+// Hiven sends text frames, but a server sending its JSON in binary frames
+// shouldn't break the connection, so binary frames holding UTF-8 are taken as
+// text.
+fn binary_as_text(message: WebsocketMessage) -> WebsocketMessage {
+	match message {
+		WebsocketMessage::Binary(bytes) => match String::from_utf8(bytes) {
+			Ok(text) => WebsocketMessage::Text(text),
+			Err(err) => WebsocketMessage::Binary(err.into_bytes())
+		},
+		message => message
+	}
+}
+
+// A gateway socket that's closed properly, with a close frame, even if the
+// gate keeper's future is dropped mid connection, say by a select! picking
+// another branch.
+//
+// Drop can't wait for the close, so it's spawned onto the runtime instead,
+// and is only as good as the runtime sticking around long enough to send it.
+// Outside of a runtime the socket is just dropped, which hiven sees as the
+// connection being reset.
+struct CloseOnDrop(Option<WebSocketStream<ConnectStream>>);
+
+impl Deref for CloseOnDrop {
+	type Target = WebSocketStream<ConnectStream>;
+
+	fn deref(&self) -> &Self::Target {
+		// Only None while being dropped.
+		self.0.as_ref().unwrap()
+	}
+}
+
+impl DerefMut for CloseOnDrop {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		self.0.as_mut().unwrap()
+	}
+}
+
+impl Drop for CloseOnDrop {
+	fn drop(&mut self) {
+		if let (Some(mut socket), Ok(runtime)) = (self.0.take(), Handle::try_current()) {
+			// Fails right away if the socket was already closed, which is fine.
+			runtime.spawn(async move {let _ = socket.close(None).await;});
+		}
+	}
+}
+
+// Waits for the next tick of an interval, or forever without one.
+async fn tick(interval: &mut Option<Interval>) {
+	match interval {
+		Some(interval) => {interval.tick().await;},
+		None => pending().await
+	}
+}
+
+// How long to wait before reconnecting. The first retry is immediate, as a
+// dropped connection is usually fine to pick straight back up, then waits
+// double from half a second up to a minute.
+fn reconnect_delay(attempt: u32) -> Duration {
+	match attempt {
+		0 => Duration::from_secs(0),
+		attempt => Duration::from_millis(500 << (attempt - 1).min(7))
+			.min(Duration::from_secs(60))
+	}
+}
+
+// Picks a random delay up to the given one, see "full jitter" in
+// https://aws.amazon.com/blogs/architecture/exponential-backoff-and-jitter/.
+fn jitter(delay: Duration) -> Duration {
+	delay.mul_f64(random::<f64>())
+}
+
+// Counters about the gateway connection, updated by the gate keeper.
+#[derive(Debug, Default)]
+struct GatewayStats {
+	connection_attempts: AtomicU64,
+	logins: AtomicU64,
+	last_disconnect: Mutex<Option<String>>,
+	connected_at: Mutex<Option<Instant>>
+}
+
+/// A handle for closing a gateway connection gracefully, sending hiven a
+/// close frame. Calling [`shutdown`] before the connection is up closes it as
+/// soon as it is.
+///
+/// [`shutdown`]: ShutdownHandle::shutdown
+#[derive(Debug, Clone)]
+pub struct ShutdownHandle {
+	notify: Arc<Notify>,
+	// Whether the gate keeper is running, so shutting down can be waited for.
+	running: Arc<WatchSender<bool>>,
+	running_receive: WatchReceiver<bool>
+}
+
+impl ShutdownHandle {
+	fn new() -> Self {
+		let (running, running_receive) = watch_channel(false);
+		Self {notify: Arc::new(Notify::new()), running: Arc::new(running), running_receive}
+	}
+
+	/// Closes the connection. The gateway then returns [`GatewayExit::Shutdown`]
+	/// once event handlers that are still running have finished.
+	pub fn shutdown(&self) {
+		self.notify.notify();
+	}
+
+	/// Closes the connection like [`shutdown`], and waits until the gateway
+	/// has stopped: the close frame has been sent, the heart beat has stopped
+	/// and event handlers that were still running have finished. Returns
+	/// straight away if the gateway isn't running.
+	///
+	/// [`shutdown`]: ShutdownHandle::shutdown
+	pub async fn shutdown_and_wait(&self) {
+		self.shutdown();
+		let mut running = self.running_receive.clone();
+		while let Some(true) = running.recv().await {}
+	}
+
+	fn running(&self) -> Running<'_> {
+		let _ = self.running.broadcast(true);
+		Running(self)
+	}
+}
+
+// Marks a gate keeper as running until dropped, including when the gateway's
+// future is dropped partway through.
+struct Running<'h>(&'h ShutdownHandle);
+
+impl Drop for Running<'_> {
+	fn drop(&mut self) {
+		let _ = self.0.running.broadcast(false);
+	}
+}
+
+/// A gateway connection that's up and logged in, returned by
+/// [`Client::connect_and_wait_ready`]. The connection only makes progress while
+/// [`wait`] or [`shutdown`] is being awaited.
+///
+/// [`wait`]: ReadyHandle::wait
+/// [`shutdown`]: ReadyHandle::shutdown
+pub struct ReadyHandle<'c> {
+	gateway: Pin<Box<dyn Future<Output = GatewayExit> + Send + 'c>>,
+	shutdown: ShutdownHandle
+}
+
+impl<'c> ReadyHandle<'c> {
+	/// Keeps the connection going until it ends, or is shut down through a
+	/// handle from [`shutdown_handle`].
+	///
+	/// [`shutdown_handle`]: ReadyHandle::shutdown_handle
+	pub async fn wait(self) -> GatewayExit {
+		self.gateway.await
+	}
+
+	/// Closes the connection gracefully, and waits for it to end.
+	pub async fn shutdown(self) -> GatewayExit {
+		self.shutdown.shutdown();
+		self.gateway.await
+	}
+
+	/// Gets a handle that can close the connection from anywhere.
+	pub fn shutdown_handle(&self) -> ShutdownHandle {
+		self.shutdown.clone()
+	}
+}
+
+// Passes the initial state on to Client::connect_and_wait_ready.
+struct ReadyHandler(Sender<EventInitState>);
+
+impl EventHandler for ReadyHandler {
+	fn on_connect<'c>(&self, _client: &'c Client, event: EventInitState) ->
+			Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		// Only the first one is waited for, later ones can be dropped.
+		let _ = self.0.clone().try_send(event);
+		Box::pin(ready(()))
+	}
+}
+
+/// Why a gateway stopped, so supervising code can decide whether to start it
+/// again.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum GatewayExit {
+	/// The gateway was shut down through a [`ShutdownHandle`].
+	Shutdown,
+	/// Hiven closed the connection before logging in with
+	/// [`AUTH_FAILED_CLOSE_CODE`], which is how it rejects a token. Starting
+	/// again with the same token won't help.
+	///
+	/// [`AUTH_FAILED_CLOSE_CODE`]: crate::gateway::AUTH_FAILED_CLOSE_CODE
+	AuthFailed,
+	/// The connection ended for any other reason, and the client wasn't set to
+	/// [reconnect](GatewayConfig::reconnect).
+	Fatal(Error)
+}
+
+/// Everything that can go wrong with a [`Client`]. New kinds of errors may be
+/// added without a major version bump, so matches need a wildcard arm.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+	ExpectationFailed(&'static str, String),
+	SocketClose(Option<CloseFrame<'static>>),
+	InternalChannelError(String),
+	Io(IOError),
+	Websocket(WebsocketError),
+	/// A request to the api failed, or hiven responded with an error status
+	/// other than a rate limit.
+	Http(HTTPError),
+	/// Hiven rate limited a request with a 429 status. Holds how long hiven
+	/// asked to wait before trying again, if it said.
+	RateLimited(Option<Duration>, HTTPError),
+	Json(JSONError),
+	/// A heart beat couldn't be written to the gateway connection, so the
+	/// connection was dropped. Unlike [`Error::SocketClose`], hiven didn't
+	/// close it.
+	HeartbeatFailed(WebsocketError),
+	/// The gateway or api couldn't be connected to within the
+	/// [connect timeout](ClientBuilder::connect_timeout).
+	ConnectTimeout,
+	/// Hiven didn't say hello and send the initial state within the
+	/// [handshake timeout](GatewayConfig::handshake_timeout).
+	HandshakeTimeout,
+	/// A domain given to [`ClientBuilder`] isn't a valid host.
+	InvalidDomain(String),
+	/// [`Client::from_env`] couldn't find a token.
+	MissingToken,
+	/// A value was out of the range hiven accepts, so the request wasn't
+	/// made. Holds what was wrong.
+	Validation(String),
+	/// Events came in faster than a [`Client::events`] stream took them, and
+	/// this many were skipped. The gateway keeps running.
+	EventsLagged(u64),
+	/// The gateway gave up after the [most reconnect attempts] in a row
+	/// failed. Holds why the last one failed.
+	///
+	/// [most reconnect attempts]: GatewayConfig::max_reconnect_attempts
+	ReconnectExhausted(Box<Error>),
+	/// A house has no room with the name given to
+	/// [`Client::send_to_room_named`].
+	RoomNotFound(String),
+	/// The gateway isn't logged in, so a frame couldn't be sent with
+	/// [`Client::gateway_send`], or the user needed by a call like
+	/// [`Client::toggle_reaction`] isn't known yet.
+	NotConnected,
+	/// [`Client::send_many`] stopped partway through. Holds the messages that
+	/// were sent, and why the next one couldn't be.
+	PartiallySent(Vec<Message>, Box<Error>),
+	/// [`Client::leave_all_houses`] couldn't leave some houses. Holds the id
+	/// of each, and why.
+	HousesNotLeft(Vec<(HouseId, Error)>)
+}
+
+impl Error {
+	pub fn expectation_failed<S>(expected: &'static str, got: S) -> Self
+			where S: Debug {
+		Self::ExpectationFailed(expected, format!("{:?}", got))
+	}
+
+	pub fn socket_close(close_data: Option<CloseFrame<'static>>) -> Self {
+		Self::SocketClose(close_data)
+	}
+
+	pub fn send_error<T>(error: SendError<T>) -> Self
+			where T: Debug {
+		Self::InternalChannelError(format!("{:?}", error))
+	}
+
+	/// Whether trying the same thing again later might work, such as after a
+	/// timeout, a dropped connection, being rate limited, or a server error.
+	pub fn is_retryable(&self) -> bool {
+		match self {
+			Self::Http(error) => error.is_timeout() || error.is_connect()
+				|| error.status().is_some_and(|status| status.is_server_error()),
+			Self::RateLimited(..) | Self::SocketClose(_) | Self::Io(_) | Self::Websocket(_) |
+				Self::HeartbeatFailed(_) | Self::ConnectTimeout |
+				Self::HandshakeTimeout | Self::NotConnected => true,
+			Self::ExpectationFailed(..) | Self::InternalChannelError(_) |
+				Self::Json(_) | Self::InvalidDomain(_) | Self::MissingToken |
+				Self::Validation(_) | Self::EventsLagged(_) |
+				Self::ReconnectExhausted(_) | Self::RoomNotFound(_) => false,
+			// Sending the rest might work, if what stopped it would.
+			Self::PartiallySent(_, err) => err.is_retryable(),
+			Self::HousesNotLeft(errors) => errors.iter().any(|(_, err)| err.is_retryable())
+		}
+	}
+
+	/// Whether hiven didn't accept the token, so it has to be replaced before
+	/// anything else will work.
+	pub fn is_auth(&self) -> bool {
+		match self {
+			Self::Http(error) => error.status() == Some(StatusCode::UNAUTHORIZED),
+			Self::PartiallySent(_, err) => err.is_auth(),
+			Self::HousesNotLeft(errors) => errors.iter().any(|(_, err)| err.is_auth()),
+			_ => false
+		}
+	}
+}
+
+impl From<IOError> for Error {
+	fn from(error: IOError) -> Self {
+		Self::Io(error)
+	}
+}
+
+impl From<HTTPError> for Error {
+	fn from(error: HTTPError) -> Self {
+		if error.is_connect() && error.is_timeout() {Self::ConnectTimeout}
+		else {Self::Http(error)}
+	}
+}
+
+impl From<JSONError> for Error {
+	fn from(error: JSONError) -> Self {
+		Self::Json(error)
+	}
+}
+
+impl From<WebsocketError> for Error {
+	fn from(error: WebsocketError) -> Self {
+		Self::Websocket(error)
+	}
+}
+
+impl<T> From<SendError<T>> for Error
+		where T: Debug {
+	fn from(error: SendError<T>) -> Self {
+		Self::send_error(error)
+	}
+}
+
+/// Reacts to events from the gateway. Every method has a default that does
+/// nothing, so only the events that matter need implementing.
+///
+/// Handlers, and the futures they return, have to be `Send`, so the gateway
+/// can be spawned onto the runtime with `tokio::spawn` like any other task.
+pub trait EventHandler: Send + Sync {
+	fn on_connect<'c>(&self, _client: &'c Client, _event: EventInitState) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		// NoOp
+		Box::pin(ready(()))
+	}
+
+	fn on_house_join<'c>(&self, _client: &'c Client, _event: House) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		// NoOp
+		Box::pin(ready(()))
+	}
+
+	fn on_house_leave<'c>(&self, _client: &'c Client, _event: EventHouseLeave) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		// NoOp
+		Box::pin(ready(()))
+	}
+
+	fn on_house_delete<'c>(&self, _client: &'c Client, _event: EventHouseDelete) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		// NoOp
+		Box::pin(ready(()))
+	}
+
+	fn on_typing<'c>(&self, _client: &'c Client, _event: EventTypingStart) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		// NoOp
+		Box::pin(ready(()))
+	}
+
+	fn on_message<'c>(&self, _client: &'c Client, _event: Message) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		// NoOp
+		Box::pin(ready(()))
+	}
+
+	/// Called when a message is pinned. `event.message` is only set if hiven
+	/// sent the message along with its ids.
+	fn on_message_pin<'c>(&self, _client: &'c Client, _event: EventMessagePin) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		// NoOp
+		Box::pin(ready(()))
+	}
+
+	/// Called when a message is unpinned. Like with pins, `event.message` is
+	/// only set if hiven sent the message along.
+	fn on_message_unpin<'c>(&self, _client: &'c Client, _event: EventMessagePin) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		// NoOp
+		Box::pin(ready(()))
+	}
+
+	/// Called when a user goes online or offline. With the `cache` feature,
+	/// the cached houses are updated before this is called.
+	fn on_presence_update<'c>(&self, _client: &'c Client, _event: EventPresenceUpdate) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		// NoOp
+		Box::pin(ready(()))
+	}
+
+	/// Called when hiven closes the gateway connection, with the close frame
+	/// it sent, if any. The frame has the close code, both as a [`CloseCode`]
+	/// and, through `u16::from`, as a number, and the reason hiven gave.
+	///
+	/// [`CloseCode`]: crate::gateway::CloseCode
+	fn on_disconnect<'c>(&self, _client: &'c Client, _close_frame: Option<CloseFrame<'static>>) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		// NoOp
+		Box::pin(ready(()))
+	}
+
+	/// Called with problems that don't stop the gateway, such as a frame only
+	/// clients should send. Problems that do stop it are returned from
+	/// [`GateKeeper::start_gateway`] instead.
+	fn on_error(&self, _client: &Client, _error: &Error) {
+		// NoOp
+	}
+
+	/// Called with every text frame from the gateway, exactly as hiven sent
+	/// it, before it's parsed. Frames that can't be parsed are passed too.
+	///
+	/// This runs on the connection's task, so it should be quick, say copying
+	/// the frame somewhere. The frame is only borrowed, so leaving this out
+	/// costs nothing.
+	fn on_raw(&self, _client: &Client, _frame: &str) {
+		// NoOp
+	}
+}
+
+/// Forwards every event to the boxed handler, so handlers of different types
+/// can be mixed in a `Vec`.
+impl<H> EventHandler for Box<H>
+		where H: EventHandler + ?Sized {
+	fn on_connect<'c>(&self, client: &'c Client, event: EventInitState) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		(**self).on_connect(client, event)
+	}
+
+	fn on_house_join<'c>(&self, client: &'c Client, event: House) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		(**self).on_house_join(client, event)
+	}
+
+	fn on_house_leave<'c>(&self, client: &'c Client, event: EventHouseLeave) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		(**self).on_house_leave(client, event)
+	}
+
+	fn on_house_delete<'c>(&self, client: &'c Client, event: EventHouseDelete) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		(**self).on_house_delete(client, event)
+	}
+
+	fn on_typing<'c>(&self, client: &'c Client, event: EventTypingStart) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		(**self).on_typing(client, event)
+	}
+
+	fn on_message<'c>(&self, client: &'c Client, event: Message) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		(**self).on_message(client, event)
+	}
+
+	fn on_message_pin<'c>(&self, client: &'c Client, event: EventMessagePin) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		(**self).on_message_pin(client, event)
+	}
+
+	fn on_message_unpin<'c>(&self, client: &'c Client, event: EventMessagePin) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		(**self).on_message_unpin(client, event)
+	}
+
+	fn on_presence_update<'c>(&self, client: &'c Client, event: EventPresenceUpdate) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		(**self).on_presence_update(client, event)
+	}
+
+	fn on_disconnect<'c>(&self, client: &'c Client, close_frame: Option<CloseFrame<'static>>) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		(**self).on_disconnect(client, close_frame)
+	}
+
+	fn on_error(&self, client: &Client, error: &Error) {
+		(**self).on_error(client, error)
+	}
+
+	fn on_raw(&self, client: &Client, frame: &str) {
+		(**self).on_raw(client, frame)
+	}
+}
+
+/// Passes every event to each handler, in order, so separate handlers (say,
+/// commands and logging) can run side by side. Each handler gets its own copy
+/// of the event, and a handler's future is finished before the next one's is
+/// polled.
+///
+/// ```rust
+/// use hiven_rs::{Client, EventHandler};
+///
+/// struct Commands;
+/// impl EventHandler for Commands {}
+/// struct Logging;
+/// impl EventHandler for Logging {}
+///
+/// let handlers: Vec<Box<dyn EventHandler>> = vec![Box::new(Commands), Box::new(Logging)];
+/// # async {
+/// Client::new("token").start_gateway(handlers).await
+/// # };
+/// ```
+impl<H> EventHandler for Vec<H>
+		where H: EventHandler {
+	fn on_connect<'c>(&self, client: &'c Client, event: EventInitState) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		in_order(self.iter().map(|handler| handler.on_connect(client, event.clone())))
+	}
+
+	fn on_house_join<'c>(&self, client: &'c Client, event: House) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		in_order(self.iter().map(|handler| handler.on_house_join(client, event.clone())))
+	}
+
+	fn on_house_leave<'c>(&self, client: &'c Client, event: EventHouseLeave) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		in_order(self.iter().map(|handler| handler.on_house_leave(client, event.clone())))
+	}
+
+	fn on_house_delete<'c>(&self, client: &'c Client, event: EventHouseDelete) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		in_order(self.iter().map(|handler| handler.on_house_delete(client, event.clone())))
+	}
+
+	fn on_typing<'c>(&self, client: &'c Client, event: EventTypingStart) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		in_order(self.iter().map(|handler| handler.on_typing(client, event.clone())))
+	}
+
+	fn on_message<'c>(&self, client: &'c Client, event: Message) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		in_order(self.iter().map(|handler| handler.on_message(client, event.clone())))
+	}
+
+	fn on_message_pin<'c>(&self, client: &'c Client, event: EventMessagePin) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		in_order(self.iter().map(|handler| handler.on_message_pin(client, event.clone())))
+	}
+
+	fn on_message_unpin<'c>(&self, client: &'c Client, event: EventMessagePin) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		in_order(self.iter().map(|handler| handler.on_message_unpin(client, event.clone())))
+	}
+
+	fn on_presence_update<'c>(&self, client: &'c Client, event: EventPresenceUpdate) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		in_order(self.iter().map(|handler| handler.on_presence_update(client, event.clone())))
+	}
+
+	fn on_disconnect<'c>(&self, client: &'c Client, close_frame: Option<CloseFrame<'static>>) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		in_order(self.iter().map(|handler| handler.on_disconnect(client, close_frame.clone())))
+	}
+
+	fn on_error(&self, client: &Client, error: &Error) {
+		for handler in self {handler.on_error(client, error)}
+	}
+
+	fn on_raw(&self, client: &Client, frame: &str) {
+		for handler in self {handler.on_raw(client, frame)}
+	}
+}
+
+/// Checks a name for something in a house, like a category, against what hiven
+/// accepts: it can't be blank, can't be longer than [`MAX_NAME_LENGTH`], and
+/// can't have control characters like line breaks. Returns
+/// [`Error::Validation`] saying what's wrong otherwise.
+///
+/// ```rust
+/// use hiven_rs::client::validate_name;
+///
+/// assert!(validate_name("Announcements").is_ok());
+/// assert!(validate_name("   ").is_err());
+/// ```
+pub fn validate_name(name: &str) -> Result<()> {
+	if name.trim().is_empty() {
+		return Err(Error::Validation("names can't be blank".to_owned()))
+	}
+
+	let length = name.chars().count();
+	if length > MAX_NAME_LENGTH {
+		return Err(Error::Validation(format!(
+			"name of {} characters is over the maximum of {}", length, MAX_NAME_LENGTH)))
+	}
+
+	match name.chars().find(|character| character.is_control()) {
+		Some(character) => Err(Error::Validation(format!(
+			"names can't have control characters like {:?}", character))),
+		None => Ok(())
+	}
+}
+
+// Awaits handler futures one after the other.
+fn in_order<'c, I>(futures: I) -> Pin<Box<dyn Future<Output = ()> + Send + 'c>>
+		where I: Iterator<Item = Pin<Box<dyn Future<Output = ()> + Send + 'c>>> {
+	let futures: Vec<_> = futures.collect();
+	Box::pin(async move {
+		for future in futures {future.await}
+	})
+}
+
+// Makes a request, waiting and trying it again while hiven rate limits it.
+// Waits as long as hiven asks, or backs off from a second when it doesn't say,
+// until it's been tried again RATE_LIMIT_MAX_RETRIES times or a wait would be
+// longer than RATE_LIMIT_MAX_DELAY. Then the last error is returned.
+async fn retry_rate_limited<F, R, T>(mut request: F) -> Result<T>
+		where F: FnMut() -> R, R: Future<Output = Result<T>> {
+	let mut backoff = Duration::from_secs(1);
+
+	for _ in 0..RATE_LIMIT_MAX_RETRIES {
+		match request().await {
+			Err(Error::RateLimited(retry_after, _))
+					if retry_after.unwrap_or(backoff) <= RATE_LIMIT_MAX_DELAY => {
+				delay_for(retry_after.unwrap_or(backoff)).await;
+				backoff *= 2;
+			},
+			result => return result
+		}
+	}
+	request().await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{
+		Client, ClientBuilder, Error, EventHandler, GateKeeper, GatewayConfig,
+		MAX_NAME_LENGTH, MAX_SLOWMODE, MessageBuilder, OutgoingFrame, TokenType,
+		is_web_url, jitter, normalize_domain, reconnect_delay, validate_name
+	};
+	use std::{sync::{Mutex, atomic::Ordering}, time::Duration};
+	use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
+
+	#[test]
+	fn api_version_test() {
+		assert_eq!(ClientBuilder::new("token").try_build().unwrap().api_base,
+			"https://api.hiven.io/v1");
+		assert_eq!(ClientBuilder::new("token").api_version(2).try_build().unwrap().api_base,
+			"https://api.hiven.io/v2");
+	}
+
+	// Checked at compile time, this only has to build.
+	#[test]
+	fn send_sync_test() {
+		fn assert_send<T: Send>(_: &T) {}
+		fn assert_sync<T: Sync>() {}
+		struct Handler;
+		impl EventHandler for Handler {}
+
+		assert_sync::<Client>();
+		let client = Client::new("token");
+		assert_send(&client.start_gateway(Handler));
+		assert_send(&client.start_gateway_until_ctrl_c(Handler));
+		let gate_keeper = GateKeeper::new(&client, Handler);
+		assert_send(&gate_keeper.start_gateway());
+	}
+
+	#[cfg(feature = "cache")]
+	#[tokio::test]
+	async fn room_not_found_test() {
+		let client = Client::new("token");
+		client.cache().insert_house(serde_json::from_value(serde_json::json!({
+			"name": "A House", "icon": null, "members": [], "id": "10", "owner_id": "1",
+			"rooms": [{"name": "general", "description": null, "position": 0, "id": "11"}]
+		})).unwrap());
+
+		match client.send_to_room_named(10_u64, "announcements", "hi").await {
+			Err(Error::RoomNotFound(name)) => assert_eq!(name, "announcements"),
+			result => panic!("expected RoomNotFound, got {:?}", result)
+		}
+	}
+
+	#[test]
+	fn not_connected_test() {
+		let client = Client::new("token");
+		match client.gateway_send(OutgoingFrame::Typing {room_id: 2.into()}) {
+			Err(Error::NotConnected) => (),
+			result => panic!("expected NotConnected, got {:?}", result)
+		}
+	}
+
+	// Serves http requests on a local port, one per connection, answering each
+	// with what `respond` makes of its first line, like `200 OK` and whatever
+	// headers and body follow. Returns the api base to point a client at, and
+	// every request as it came in.
+	async fn serve<F>(respond: F) -> (String, UnboundedReceiver<String>)
+			where F: Fn(&str) -> String + Send + 'static {
+		use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpListener};
+
+		let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let api_base = format!("http://{}/v1", listener.local_addr().unwrap());
+		let (requests, received) = unbounded_channel();
+		tokio::spawn(async move {
+			loop {
+				let (mut stream, _) = listener.accept().await.unwrap();
+				let mut request = [0; 1024];
+				let length = stream.read(&mut request).await.unwrap();
+				let request = String::from_utf8_lossy(&request[..length]).into_owned();
+				// Close each connection, so every request comes in on a new one.
+				let response = respond(request.lines().next().unwrap_or_default())
+					.replacen("\r\n", "\r\nconnection: close\r\n", 1);
+				let _ = requests.send(request);
+				stream.write_all(format!("HTTP/1.1 {}", response).as_bytes()).await.unwrap();
+			}
+		});
+		(api_base, received)
+	}
+
+	// Answers with each of the responses in turn, for `serve`.
+	fn in_turn(responses: Vec<String>) -> impl Fn(&str) -> String + Send {
+		let responses = Mutex::new(responses.into_iter());
+		move |_| responses.lock().unwrap().next().expect("more requests than responses")
+	}
+
+	// The first lines of the next `count` requests `serve` got.
+	async fn request_lines(received: &mut UnboundedReceiver<String>, count: usize) ->
+			Vec<String> {
+		let mut lines = Vec::new();
+		for _ in 0..count {
+			lines.push(received.recv().await.unwrap().lines().next().unwrap().to_owned());
+		}
+		lines
+	}
+
+	// Answers a single http request with the given status.
+	async fn respond_with(status: &str) -> String {
+		serve(in_turn(vec![format!("{}\r\ncontent-length: 0\r\n\r\n", status)])).await.0
+	}
+
+	#[cfg(feature = "cache")]
+	#[tokio::test]
+	async fn etag_test() {
+		let body = r#"{"data": {"name": "general", "description": null,
+			"position": 0, "id": "11"}}"#;
+		let (api_base, mut received) = serve(in_turn(vec![
+			format!("200 OK\r\netag: \"v1\"\r\ncontent-length: {}\r\n\r\n{}",
+				body.len(), body),
+			"304 Not Modified\r\n\r\n".to_owned()
+		])).await;
+		let mut client = Client::new("token");
+		client.api_base = api_base;
+
+		assert_eq!(client.get_room(11_u64).await.unwrap().name, "general");
+		assert_eq!(client.get_room(11_u64).await.unwrap().name, "general");
+		assert!(!received.recv().await.unwrap().to_lowercase().contains("if-none-match"));
+		assert!(received.recv().await.unwrap().to_lowercase()
+			.contains("if-none-match: \"v1\""));
+	}
+
+	#[tokio::test]
+	async fn leave_all_houses_test() {
+		let house = |id| format!(r#"{{"name": "A House", "icon": null, "members": [],
+			"rooms": [], "id": "{}", "owner_id": "1"}}"#, id);
+		let houses = format!(r#"{{"data": [{}, {}, {}]}}"#, house(10), house(20), house(30));
+		// The owned house can't be left, the others can.
+		let (api_base, mut received) = serve(move |request| match request {
+			"GET /v1/users/@me/houses HTTP/1.1" =>
+				format!("200 OK\r\ncontent-length: {}\r\n\r\n{}", houses.len(), houses),
+			"DELETE /v1/houses/20/members/@me HTTP/1.1" =>
+				"403 Forbidden\r\ncontent-length: 0\r\n\r\n".to_owned(),
+			_ => "204 No Content\r\n\r\n".to_owned()
+		}).await;
+		let mut client = Client::new("token");
+		client.api_base = api_base;
+
+		match client.leave_all_houses().await {
+			Err(Error::HousesNotLeft(failed)) => {
+				assert_eq!(failed.len(), 1);
+				assert_eq!(failed[0].0, 20);
+				assert!(!failed[0].1.is_retryable());
+			},
+			result => panic!("expected HousesNotLeft, got {:?}", result)
+		}
+
+		let mut requests = request_lines(&mut received, 4).await;
+		requests.sort();
+		assert_eq!(requests, [
+			"DELETE /v1/houses/10/members/@me HTTP/1.1",
+			"DELETE /v1/houses/20/members/@me HTTP/1.1",
+			"DELETE /v1/houses/30/members/@me HTTP/1.1",
+			"GET /v1/users/@me/houses HTTP/1.1"
+		]);
+	}
+
+	#[tokio::test]
+	async fn keep_typing_test() {
+		let (api_base, mut received) = serve(|_| "204 No Content\r\n\r\n".to_owned()).await;
+		let mut client = Client::new("token");
+		client.api_base = api_base;
+
+		let guard = client.keep_typing_every(2.into(), Duration::from_millis(20));
+		assert_eq!(request_lines(&mut received, 3).await,
+			["POST /v1/rooms/2/typing HTTP/1.1"; 3]);
+		drop(guard);
+		// Anything already on its way can still arrive, but then it stops.
+		tokio::time::delay_for(Duration::from_millis(30)).await;
+		while received.try_recv().is_ok() {}
+		tokio::time::delay_for(Duration::from_millis(100)).await;
+		assert!(received.try_recv().is_err());
+	}
+
+	#[tokio::test]
+	async fn raw_request_test() {
+		use reqwest::Method;
+		use serde_json::json;
+
+		let (api_base, mut received) = serve(in_turn(vec![
+			"200 OK\r\ncontent-length: 10\r\n\r\n{\"new\": 1}".to_owned()
+		])).await;
+		let mut client = Client::new("token");
+		client.api_base = api_base;
+
+		let response = client.raw_request(Method::PATCH, "/houses/1/new-thing",
+			Some(json!({"enabled": true}))).await.unwrap();
+		assert_eq!(response.text().await.unwrap(), r#"{"new": 1}"#);
+		let request = received.recv().await.unwrap();
+		assert!(request.starts_with("PATCH /v1/houses/1/new-thing HTTP/1.1"));
+		let request = request.to_lowercase();
+		assert!(request.contains("authorization: token"));
+		assert!(request.contains("content-type: application/json"));
+		assert!(request.ends_with(r#"{"enabled":true}"#));
+
+		assert!(matches!(client.raw_request(Method::GET, "houses", None).await,
+			Err(Error::Validation(_))));
+	}
+
+	#[tokio::test]
+	async fn gateway_bot_info_test() {
+		let body = r#"{"data": {"url": "wss://swarm-dev.hiven.io", "shards": 2}}"#;
+		let (api_base, mut received) = serve(in_turn(vec![
+			format!("200 OK\r\ncontent-length: {}\r\n\r\n{}", body.len(), body)
+		])).await;
+		let mut client = Client::new("token");
+		client.api_base = api_base;
+
+		let info = client.gateway_bot_info().await.unwrap();
+		assert_eq!((info.url.as_str(), info.shards), ("wss://swarm-dev.hiven.io", 2));
+		assert_eq!(request_lines(&mut received, 1).await, ["GET /v1/gateway/bot HTTP/1.1"]);
+	}
+
+	#[tokio::test]
+	async fn messages_iter_test() {
+		use futures::stream::StreamExt;
+
+		let message = |id| format!(r#"{{"id": "{}", "room_id": "2", "author_id": "1",
+			"content": "message {}"}}"#, id, id);
+		let page = format!(r#"{{"data": [{}, {}]}}"#, message(4), message(5));
+		let (api_base, mut received) = serve(in_turn(vec![
+			"429 Too Many Requests\r\nretry-after: 0\r\ncontent-length: 0\r\n\r\n"
+				.to_owned(),
+			format!("200 OK\r\ncontent-length: {}\r\n\r\n{}", page.len(), page),
+			"200 OK\r\ncontent-length: 12\r\n\r\n{\"data\": []}".to_owned()
+		])).await;
+		let mut client = Client::new("token");
+		client.api_base = api_base;
+
+		// Being rate limited on the first page doesn't end the stream.
+		let messages: Vec<_> = client.messages_iter(2_u64)
+			.map(|message| message.unwrap().id).collect().await;
+		assert_eq!(messages, [5, 4]);
+		assert_eq!(request_lines(&mut received, 3).await, [
+			"GET /v1/rooms/2/messages HTTP/1.1",
+			"GET /v1/rooms/2/messages HTTP/1.1",
+			"GET /v1/rooms/2/messages?before=4 HTTP/1.1"
+		]);
+	}
+
+	#[tokio::test]
+	async fn retry_after_test() {
+		use reqwest::Method;
+
+		let rate_limited = |seconds| format!("429 Too Many Requests\r\n\
+			retry-after: {}\r\ncontent-length: 0\r\n\r\n", seconds);
+		let (api_base, mut received) = serve(in_turn(vec![rate_limited(0),
+			"200 OK\r\ncontent-length: 0\r\n\r\n".to_owned(), rate_limited(60)])).await;
+		let mut client = Client::new("token");
+		client.api_base = api_base;
+
+		// Hiven asked to wait no time at all, so it's tried again right away.
+		assert!(client.raw_request(Method::GET, "/houses/1", None).await.is_ok());
+		// Waiting a minute is too long, so it's given up on.
+		match client.raw_request(Method::GET, "/houses/1", None).await {
+			Err(Error::RateLimited(Some(retry_after), _)) =>
+				assert_eq!(retry_after, Duration::from_secs(60)),
+			result => panic!("expected RateLimited, got {:?}", result)
+		}
+		assert_eq!(request_lines(&mut received, 3).await.len(), 3);
+	}
+
+	#[tokio::test]
+	async fn rate_limit_retries_test() {
+		use reqwest::Method;
+
+		let (api_base, mut received) = serve(|_| "429 Too Many Requests\r\n\
+			retry-after: 1\r\ncontent-length: 0\r\n\r\n".to_owned()).await;
+		let mut client = Client::new("token");
+		client.api_base = api_base;
+
+		// Hiven never stops asking for a short wait, so it's given up on after a
+		// few tries instead of waiting forever.
+		let result = tokio::time::timeout(Duration::from_secs(20),
+			client.raw_request(Method::GET, "/houses/1", None)).await.unwrap();
+		assert!(matches!(result, Err(Error::RateLimited(Some(_), _))));
+		assert_eq!(request_lines(&mut received, 7).await.len(), 7);
+		assert!(received.try_recv().is_err());
+	}
+
+	#[tokio::test]
+	async fn toggle_reaction_test() {
+		let users = r#"{"data": [{"username": "seven", "name": "Seven", "icon": null,
+			"header": null, "id": "7"}]}"#;
+		let (api_base, mut received) = serve(in_turn(vec![
+			format!("200 OK\r\ncontent-length: {}\r\n\r\n{}", users.len(), users),
+			"200 OK\r\ncontent-length: 12\r\n\r\n{\"data\": []}".to_owned(),
+			"204 No Content\r\n\r\n".to_owned()
+		])).await;
+		let mut client = Client::new("token");
+		client.api_base = api_base;
+		assert!(matches!(client.toggle_reaction(2_u64, 3, "👍").await,
+			Err(Error::NotConnected)));
+		client.user_id.store(7, Ordering::Release);
+
+		// The user already reacted, so the reaction is taken back.
+		assert!(!client.toggle_reaction(2_u64, 3, "👍").await.unwrap());
+		assert_eq!(request_lines(&mut received, 3).await, [
+			"GET /v1/rooms/2/messages/3/reactions/%F0%9F%91%8D/users HTTP/1.1",
+			"GET /v1/rooms/2/messages/3/reactions/%F0%9F%91%8D/users?after=7 HTTP/1.1",
+			"DELETE /v1/rooms/2/messages/3/reactions/%F0%9F%91%8D/@me HTTP/1.1"
+		]);
+	}
+
+	#[tokio::test]
+	async fn error_kind_test() {
+		let client = Client::new("token");
+		let client = &client;
+		let error = |status| async move {
+			client.fetch_url(&respond_with(status).await).await.unwrap_err()
+		};
+
+		let unauthorized = error("401 Unauthorized").await;
+		assert!(unauthorized.is_auth() && !unauthorized.is_retryable());
+		let rate_limited = error("429 Too Many Requests").await;
+		assert!(rate_limited.is_retryable() && !rate_limited.is_auth());
+		assert!(error("503 Service Unavailable").await.is_retryable());
+		let not_found = error("404 Not Found").await;
+		assert!(!not_found.is_retryable() && !not_found.is_auth());
+
+		assert!(Error::HandshakeTimeout.is_retryable());
+		assert!(Error::ConnectTimeout.is_retryable());
+		assert!(!Error::InvalidDomain(String::new()).is_retryable());
+		assert!(!Error::ReconnectExhausted(Box::new(Error::HandshakeTimeout))
+			.is_retryable());
+	}
+
+	#[test]
+	fn jitter_test() {
+		for attempt in 0..10 {
+			let delay = reconnect_delay(attempt);
+			assert!(jitter(delay) <= delay);
+		}
+		assert_eq!(jitter(Duration::from_secs(0)), Duration::from_secs(0));
+	}
+
+	#[test]
+	fn normalize_domain_test() {
+		let normalize = |domain: &str| normalize_domain(domain.to_owned()).unwrap();
+
+		assert_eq!(normalize("api.hiven.io"), (None, "api.hiven.io".to_owned()));
+		assert_eq!(normalize("https://api.hiven.io/"),
+			(Some(true), "api.hiven.io".to_owned()));
+		assert_eq!(normalize("wss://swarm-dev.hiven.io"),
+			(Some(true), "swarm-dev.hiven.io".to_owned()));
+		assert_eq!(normalize("http://127.0.0.1:8080//"),
+			(Some(false), "127.0.0.1:8080".to_owned()));
+	}
+
+	#[test]
+	fn web_url_test() {
+		assert!(is_web_url("https://example.com/cat.png"));
+		assert!(is_web_url("http://127.0.0.1:8080/a?b=c"));
+		assert!(!is_web_url("file:///etc/passwd"));
+		assert!(!is_web_url("example.com/cat.png"));
+		assert!(!is_web_url("https://"));
+	}
+
+	#[tokio::test]
+	async fn attachment_url_validation_test() {
+		let message = MessageBuilder::new("look").attachment_url("not a url");
+		match Client::new("token").send_message_with(1_u64, message).await {
+			Err(Error::Validation(_)) => (),
+			result => panic!("expected Validation, got {:?}", result)
+		}
+	}
+
+	#[tokio::test]
+	async fn name_validation_test() {
+		assert!(validate_name("general").is_ok());
+		assert!(validate_name(&"é".repeat(MAX_NAME_LENGTH)).is_ok());
+		for name in &["", " \t", "line\nbreak", &"a".repeat(MAX_NAME_LENGTH + 1)] {
+			assert!(matches!(validate_name(name), Err(Error::Validation(_))), "{:?}", name);
+		}
+
+		let client = Client::new("token");
+		match client.create_category(1_u64, "").await {
+			Err(Error::Validation(_)) => (),
+			result => panic!("expected Validation, got {:?}", result)
+		}
+		match client.edit_category(1_u64, 2, "a\u{0}b").await {
+			Err(Error::Validation(_)) => (),
+			result => panic!("expected Validation, got {:?}", result)
+		}
+	}
+
+	#[tokio::test]
+	async fn slowmode_validation_test() {
+		match Client::new("token").set_slowmode(1_u64, MAX_SLOWMODE + 1).await {
+			Err(Error::Validation(_)) => (),
+			result => panic!("expected Validation, got {:?}", result)
+		}
+	}
+
+	#[test]
+	fn gateway_config_test() {
+		let client = ClientBuilder::new("token")
+			.gateway_config(GatewayConfig::default().reconnect(true).reconnect_jitter(false))
+			.max_reconnect_attempts(Some(3)).try_build().unwrap();
+
+		assert!(client.gateway.reconnect && !client.gateway.reconnect_jitter);
+		assert_eq!(client.gateway.max_reconnect_attempts, Some(3));
+		assert_eq!(client.gateway.handshake_timeout, Duration::from_secs(10));
+	}
+
+	#[test]
+	fn token_type_test() {
+		assert_eq!(Client::new("abc").token(), "abc");
+		let client = ClientBuilder::new("abc").token_type(TokenType::Bearer).try_build()
+			.unwrap();
+		assert_eq!(client.token(), "Bearer abc");
+		client.set_token("def".to_owned());
+		assert_eq!(client.token(), "Bearer def");
+		// Only the token counts as being present, not the scheme.
+		client.set_token(String::new());
+		assert!(!client.token_present());
+	}
+
+	#[test]
+	fn from_env_test() {
+		// Variables are looked up in a list, so the process's environment, which
+		// other tests share, is left alone.
+		let from_env = |variables: &[(&str, &str)]| Client::from_env_with(|name| {
+			variables.iter().find(|(variable, _)| *variable == name)
+				.map(|(_, value)| (*value).to_owned())
+		});
+
+		assert!(matches!(from_env(&[]), Err(Error::MissingToken)));
+		assert!(matches!(from_env(&[("HIVEN_TOKEN", "")]), Err(Error::MissingToken)));
+
+		let client = from_env(&[("HIVEN_TOKEN", "token")]).unwrap();
+		assert!(client.token_present());
+		assert_eq!(client.api_host(), "api.hiven.io");
+
+		let client = from_env(&[("HIVEN_TOKEN", "token"),
+			("HIVEN_GATEWAY_HOST", "localhost:8081")]).unwrap();
+		assert_eq!(client.api_host(), "api.hiven.io");
+		assert_eq!(client.gateway_host(), "localhost:8081");
+	}
+
+	#[test]
+	fn set_domains_test() {
+		let mut client = ClientBuilder::new("token").api_version(2).try_build().unwrap();
+		client.set_domains("http://localhost:8080/", "localhost:8081").unwrap();
+		assert_eq!(client.api_host(), "localhost:8080");
+		assert_eq!(client.gateway_host(), "localhost:8081");
+		assert_eq!(client.api_base, "http://localhost:8080/v2");
+		assert_eq!(client.gateway_scheme, "wss");
+
+		assert!(client.set_domains("api.hiven.io", "not a host").is_err());
+		assert_eq!(client.api_host(), "localhost:8080");
+
+		// Without a scheme, the one in use is kept.
+		client.set_domains("localhost:8082", "ws://localhost:8083").unwrap();
+		assert_eq!(client.api_base, "http://localhost:8082/v2");
+		assert_eq!(client.gateway_scheme, "ws");
+	}
+
+	#[test]
+	fn invalid_domain_test() {
+		for domain in ["", "https://", "swarm-dev.hiven.io/socket", "api hiven io",
+				"ftp://api.hiven.io"].iter() {
+			match normalize_domain((*domain).to_owned()) {
+				Err(Error::InvalidDomain(invalid)) => assert_eq!(invalid, *domain),
+				result => panic!("expected InvalidDomain, got {:?}", result)
+			}
+		}
+		assert!(matches!(Client::new_at("token", "api hiven io", "swarm-dev.hiven.io"),
+			Err(Error::InvalidDomain(_))));
 	}
 }
@@ -0,0 +1,107 @@
+//! A minimal prefix command dispatcher, built on [`EventHandler::on_message`].
+//!
+//! Only available with the `commands` feature. Messages starting with the
+//! prefix are split on whitespace, the first word picks the command, and the
+//! rest are passed to it as arguments. Anything else is ignored.
+//!
+//! ```rust
+//! use hiven_rs::{Client, commands::CommandHandler};
+//!
+//! let handler = CommandHandler::new("!")
+//!     .command("ping", |client, message, _args| Box::pin(async move {
+//!         let _ = client.send_message(message.room_id, "Pong!").await;
+//!     }))
+//!     .command("echo", |client, message, args| Box::pin(async move {
+//!         let _ = client.send_message(message.room_id, args.join(" ")).await;
+//!     }));
+//!
+//! # async {
+//! Client::new("token").start_gateway(handler).await
+//! # };
+//! ```
+//!
+//! [`EventHandler::on_message`]: crate::client::EventHandler::on_message
+
+use self::super::{
+	client::{Client, EventHandler},
+	data::Message
+};
+use std::{collections::HashMap, future::{Future, ready}, pin::Pin};
+
+type Command = Box<dyn for<'c> Fn(&'c Client, Message, Vec<String>) ->
+	Pin<Box<dyn Future<Output = ()> + Send + 'c>> + Send + Sync>;
+
+/// An event handler that routes prefixed messages to commands.
+pub struct CommandHandler {
+	prefix: String,
+	commands: HashMap<String, Command>
+}
+
+impl CommandHandler {
+	/// Creates a handler without any commands, for messages starting with
+	/// `prefix`.
+	pub fn new<P>(prefix: P) -> Self
+			where P: Into<String> {
+		Self {
+			prefix: prefix.into(),
+			commands: HashMap::new()
+		}
+	}
+
+	/// Registers a command, replacing any command with the same name. The
+	/// command is called with the client, the message, and the words after the
+	/// command's name.
+	pub fn command<N, F>(mut self, name: N, command: F) -> Self
+			where N: Into<String>,
+				F: for<'c> Fn(&'c Client, Message, Vec<String>) ->
+					Pin<Box<dyn Future<Output = ()> + Send + 'c>> + Send + Sync + 'static {
+		self.commands.insert(name.into(), Box::new(command));
+		self
+	}
+}
+
+impl EventHandler for CommandHandler {
+	fn on_message<'c>(&self, client: &'c Client, event: Message) ->
+			Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+		let (command, args) = match parse(&self.prefix, &event.content) {
+			Some((name, args)) => match self.commands.get(name) {
+				Some(command) => (command, args.into_iter().map(str::to_owned).collect()),
+				None => return Box::pin(ready(()))
+			},
+			None => return Box::pin(ready(()))
+		};
+
+		command(client, event, args)
+	}
+}
+
+// Splits a message into a command name and its arguments, if it starts with
+// the prefix.
+fn parse<'m>(prefix: &str, content: &'m str) -> Option<(&'m str, Vec<&'m str>)> {
+	let rest = content.strip_prefix(prefix)?;
+	// Nothing can come between the prefix and the name.
+	if rest.starts_with(char::is_whitespace) {return None}
+
+	let mut words = rest.split_whitespace();
+	Some((words.next()?, words.collect()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::parse;
+
+	#[test]
+	fn parse_test() {
+		assert_eq!(parse("!", "!ping"), Some(("ping", vec![])));
+		assert_eq!(parse("!", "!echo hello  there "), Some(("echo", vec!["hello", "there"])));
+		assert_eq!(parse("$$", "$$roll\n2d6"), Some(("roll", vec!["2d6"])));
+	}
+
+	#[test]
+	fn parse_ignored_test() {
+		assert_eq!(parse("!", "ping"), None);
+		assert_eq!(parse("!", "!"), None);
+		assert_eq!(parse("!", "! ping"), None);
+		assert_eq!(parse("!", "hey !ping"), None);
+	}
+}
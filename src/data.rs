@@ -1,71 +1,1160 @@
-use self::super::util::{from_str, from_str_opt};
-use serde::{Deserialize, Serialize};
+use self::super::{
+	client::{Client, Error as ClientError},
+	markdown::strip_markdown_with,
+	util::{from_str, from_str_opt, from_str_seq}
+};
+use bitflags::bitflags;
+use reqwest::Response as HTTPResponse;
+use serde::{
+	Deserialize, Deserializer, Serialize, Serializer,
+	de::{Error as DeserializeError, Unexpected, Visitor}
+};
+use serde_json::Value as JSONValue;
+#[cfg(feature = "preserve-unknown")]
+use std::collections::HashMap;
+use std::{
+	cmp::Ordering,
+	error::Error as STDError,
+	hash::{Hash, Hasher},
+	fmt::{Display, Formatter, Result as FMTResult},
+	num::ParseIntError,
+	str::FromStr,
+	time::{Duration, SystemTime, UNIX_EPOCH}
+};
 
-#[derive(Debug, Deserialize, Serialize)]
+const WEB_CLIENT: &str = "https://hiven.io";
+
+const MEDIA: &str = "https://media.hiven.io/v1";
+
+// Builds the url of an image hiven stores by hash, such as an icon. Full urls
+// are passed through as is, and anything that's neither is dropped.
+fn media_url(owner: &str, id: u64, kind: &str, image: &Option<String>) ->
+		Option<String> {
+	let image = image.as_deref()?;
+	if image.starts_with("https://") || image.starts_with("http://") {
+		return Some(image.to_owned())
+	}
+
+	let hash = |character: char| character.is_ascii_alphanumeric()
+		|| "-_.".contains(character);
+	if image.is_empty() || !image.chars().all(hash) {return None}
+	Some(format!("{}/{}/{}/{}/{}", MEDIA, owner, id, kind, image))
+}
+
+// Private rooms aren't in a house, so their links don't have one.
+fn room_url(house_id: Option<HouseId>, room_id: RoomId) -> String {
+	match house_id {
+		Some(house_id) => format!("{}/houses/{}/rooms/{}", WEB_CLIENT, house_id, room_id),
+		None => format!("{}/rooms/{}", WEB_CLIENT, room_id)
+	}
+}
+
+/// When hiven's snowflakes start counting from, the start of 2018, in
+/// milliseconds since the unix epoch.
+pub const HIVEN_EPOCH: u64 = 1_514_764_800_000;
+
+/// An id, as hiven makes them. The upper 42 bits are when the id was made, in
+/// milliseconds since [`HIVEN_EPOCH`], so ids sort by age.
+///
+/// Hiven sends ids as strings, which is how snowflakes are serialized, but
+/// they can be deserialized from numbers too. The ids of houses, rooms,
+/// messages and users have their own types on top of it, like [`HouseId`],
+/// and the rest are plain `u64`s.
+///
+/// ```rust
+/// use hiven_rs::data::Snowflake;
+///
+/// let id: Snowflake = "175036227875828737".parse().unwrap();
+/// assert_eq!(u64::from(id), 175036227875828737);
+/// assert_eq!(id.to_string(), "175036227875828737");
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Snowflake(pub u64);
+
+impl Snowflake {
+	/// When the id was made, in milliseconds since the unix epoch.
+	pub fn timestamp(self) -> u64 {
+		(self.0 >> 22) + HIVEN_EPOCH
+	}
+
+	/// When the id was made.
+	pub fn created_at(self) -> SystemTime {
+		UNIX_EPOCH + Duration::from_millis(self.timestamp())
+	}
+}
+
+impl Display for Snowflake {
+	fn fmt(&self, formatter: &mut Formatter) -> FMTResult {
+		write!(formatter, "{}", self.0)
+	}
+}
+
+impl FromStr for Snowflake {
+	type Err = ParseIntError;
+
+	fn from_str(id: &str) -> Result<Self, Self::Err> {
+		id.parse().map(Self)
+	}
+}
+
+impl From<u64> for Snowflake {
+	fn from(id: u64) -> Self {
+		Self(id)
+	}
+}
+
+impl From<Snowflake> for u64 {
+	fn from(id: Snowflake) -> u64 {
+		id.0
+	}
+}
+
+impl<'d> Deserialize<'d> for Snowflake {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+			where D: Deserializer<'d> {
+		deserializer.deserialize_any(SnowflakeVisitor)
+	}
+}
+
+impl Serialize for Snowflake {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+			where S: Serializer {
+		serializer.collect_str(self)
+	}
+}
+
+struct SnowflakeVisitor;
+
+impl<'d> Visitor<'d> for SnowflakeVisitor {
+	type Value = Snowflake;
+
+	fn expecting(&self, formatter: &mut Formatter) -> FMTResult {
+		write!(formatter, "an id as a string or an integer")
+	}
+
+	fn visit_str<E>(self, id: &str) -> Result<Self::Value, E>
+			where E: DeserializeError {
+		id.parse().map_err(|_| E::invalid_value(Unexpected::Str(id), &self))
+	}
+
+	fn visit_u64<E>(self, id: u64) -> Result<Self::Value, E>
+			where E: DeserializeError {
+		Ok(Snowflake(id))
+	}
+}
+
+// The ids of different things are all snowflakes, but each gets its own type
+// so a room's id can't be passed where a house's is wanted.
+macro_rules! typed_id {
+	($($(#[$attribute:meta])* $name:ident),*) => {$(
+		$(#[$attribute])*
+		#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+		#[derive(Deserialize, Serialize)]
+		#[serde(transparent)]
+		pub struct $name(pub Snowflake);
+
+		impl $name {
+			/// When the id was made.
+			pub fn created_at(self) -> SystemTime {
+				self.0.created_at()
+			}
+		}
+
+		impl Display for $name {
+			fn fmt(&self, formatter: &mut Formatter) -> FMTResult {
+				self.0.fmt(formatter)
+			}
+		}
+
+		impl FromStr for $name {
+			type Err = ParseIntError;
+
+			fn from_str(id: &str) -> Result<Self, Self::Err> {
+				id.parse().map(Self)
+			}
+		}
+
+		impl From<u64> for $name {
+			fn from(id: u64) -> Self {
+				Self(Snowflake(id))
+			}
+		}
+
+		impl From<Snowflake> for $name {
+			fn from(id: Snowflake) -> Self {
+				Self(id)
+			}
+		}
+
+		impl From<$name> for u64 {
+			fn from(id: $name) -> u64 {
+				id.0.0
+			}
+		}
+
+		impl PartialEq<u64> for $name {
+			fn eq(&self, id: &u64) -> bool {
+				self.0.0 == *id
+			}
+		}
+	)*};
+}
+
+typed_id! {
+	/// A house's id. See [`Snowflake`].
+	HouseId,
+	/// The id of a room, in a house or private. See [`Snowflake`].
+	RoomId,
+	/// A message's id. See [`Snowflake`].
+	MessageId,
+	/// A user's id. See [`Snowflake`].
+	UserId
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct House {
 	pub name: String,
 	pub icon: Option<String>,
 	pub members: Vec<Member>,
 	pub rooms: Vec<Room>,
-	#[serde(deserialize_with = "from_str")]
-	pub id: u64,
-	#[serde(deserialize_with = "from_str")]
-	pub owner_id: u64
+	/// The groups the house's rooms are arranged in. Hiven calls these
+	/// entities.
+	#[serde(default, alias = "entities")]
+	pub categories: Vec<Category>,
+	#[serde(default)]
+	pub roles: Vec<Role>,
+	pub id: HouseId,
+	pub owner_id: UserId,
+	/// Fields this version doesn't model yet, kept as they were sent. Only
+	/// available with the `preserve-unknown` feature.
+	#[cfg(feature = "preserve-unknown")]
+	#[serde(flatten)]
+	pub extra: HashMap<String, JSONValue>
+}
+
+impl House {
+	/// The full url of the house's icon, if it has one.
+	pub fn icon_url(&self) -> Option<String> {
+		media_url("houses", self.id.into(), "icons", &self.icon)
+	}
+
+	/// The rooms in a category, in no particular order.
+	pub fn rooms_in(&self, category_id: u64) -> impl Iterator<Item = &Room> {
+		self.rooms.iter().filter(move |room| room.parent_id == Some(category_id))
+	}
+
+	/// How many members the house has, as far as hiven sent them.
+	pub fn member_count(&self) -> usize {
+		self.members.len()
+	}
+
+	/// How many of the house's members are online.
+	pub fn online_count(&self) -> usize {
+		self.online_members().len()
+	}
+
+	/// The house's members that are known to be online.
+	pub fn online_members(&self) -> Vec<&Member> {
+		self.members.iter()
+			.filter(|member| member.presence == Some(Presence::Online)).collect()
+	}
+
+	/// Whether a member of the house is online. `None` if they aren't a member,
+	/// or hiven hasn't said what their presence is.
+	pub fn is_online<U>(&self, user: U) -> Option<bool>
+			where U: Into<UserId> {
+		let user_id = user.into();
+		let member = self.members.iter().find(|member| member.user.id == user_id)?;
+		Some(member.presence? == Presence::Online)
+	}
+}
+
+impl From<&House> for HouseId {
+	fn from(house: &House) -> HouseId {
+		house.id
+	}
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Member {
 	pub user: User,
-	pub presence: Presence
+	/// `None` if hiven didn't send one, which it often doesn't for members,
+	/// until a `PRESENCE_UPDATE` says what it is.
+	#[serde(default)]
+	pub presence: Option<Presence>
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Role {
+	pub name: String,
+	#[serde(default)]
+	pub color: Option<Color>,
+	#[serde(default)]
+	pub position: usize,
+	#[serde(deserialize_with = "from_str")]
+	pub id: u64
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Room {
 	pub name: String,
 	pub description: Option<String>,
 	//pub emoji:
 	pub position: usize,
+	#[serde(default, rename = "type")]
+	pub room_type: RoomType,
+	#[serde(default)]
+	pub last_message_id: Option<MessageId>,
 	#[serde(default)]
+	pub house_id: Option<HouseId>,
+	/// How many seconds members have to wait between messages, or 0 without
+	/// slowmode.
+	#[serde(default, alias = "rate_limit_per_user")]
+	pub slowmode: u32,
+	/// The category the room is in, if any.
+	#[serde(default, alias = "parent_entity_id")]
 	#[serde(deserialize_with = "from_str_opt")]
-	pub last_message_id: Option<u64>,
+	pub parent_id: Option<u64>,
+	pub id: RoomId,
+	/// Fields this version doesn't model yet, kept as they were sent. Only
+	/// available with the `preserve-unknown` feature.
+	#[cfg(feature = "preserve-unknown")]
+	#[serde(flatten)]
+	pub extra: HashMap<String, JSONValue>
+}
+
+impl Room {
+	/// A link to the room in hiven's web client.
+	pub fn url(&self) -> String {
+		room_url(self.house_id, self.id)
+	}
+}
+
+/// What kind of room a room in a house is.
+///
+/// Types this version doesn't know about yet are kept as `Unknown`, and types
+/// that aren't a number at all as `Other`, so a new kind of room doesn't break
+/// parsing the house it's in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RoomType {
+	/// A room for sending messages in.
+	#[default]
+	Text,
+	/// A portal, hiven's voice rooms.
+	Portal,
+	Unknown(u64),
+	/// A type that wasn't a number, like a name or null.
+	Other
+}
+
+impl RoomType {
+	/// The number hiven uses for the type, or `None` for [`RoomType::Other`].
+	pub fn number(self) -> Option<u64> {
+		match self {
+			Self::Text => Some(0),
+			Self::Portal => Some(1),
+			Self::Unknown(room_type) => Some(room_type),
+			Self::Other => None
+		}
+	}
+}
+
+impl From<u64> for RoomType {
+	fn from(room_type: u64) -> Self {
+		match room_type {
+			0 => Self::Text,
+			1 => Self::Portal,
+			room_type => Self::Unknown(room_type)
+		}
+	}
+}
+
+impl<'d> Deserialize<'d> for RoomType {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+			where D: Deserializer<'d> {
+		// Numbers that come as strings, like ids do, are still numbers.
+		Ok(match JSONValue::deserialize(deserializer)? {
+			JSONValue::Number(number) => number.as_u64().map_or(Self::Other, Self::from),
+			JSONValue::String(string) => string.parse::<u64>().map_or(Self::Other, Self::from),
+			_ => Self::Other
+		})
+	}
+}
+
+impl Serialize for RoomType {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+			where S: Serializer {
+		match self.number() {
+			Some(number) => serializer.serialize_u64(number),
+			None => serializer.serialize_none()
+		}
+	}
+}
+
+impl From<Room> for RoomId {
+	fn from(room: Room) -> RoomId {
+		room.id
+	}
+}
+
+/// A group of rooms in a house, see [`House::rooms_in`].
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Category {
+	pub name: String,
+	#[serde(default)]
+	pub position: usize,
 	#[serde(deserialize_with = "from_str")]
 	pub id: u64
 }
 
-impl From<Room> for u64 {
-	fn from(room: Room) -> u64 {
+/// A direct message or group room, outside of any house.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PrivateRoom {
+	pub name: Option<String>,
+	/// Users in the room, other than the client's user.
+	#[serde(default)]
+	pub recipients: Vec<User>,
+	#[serde(default)]
+	pub last_message_id: Option<MessageId>,
+	pub id: RoomId
+}
+
+impl From<PrivateRoom> for RoomId {
+	fn from(room: PrivateRoom) -> RoomId {
 		room.id
 	}
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Message {
 	pub content: String,
+	#[serde(default)]
+	pub embed: Option<Embed>,
+	pub id: MessageId,
+	pub room_id: RoomId,
+	/// The house the message was sent in, `None` in private rooms.
+	#[serde(default)]
+	pub house_id: Option<HouseId>,
+	pub author_id: UserId,
+	#[serde(default)]
+	pub flags: MessageFlags,
+	#[serde(default, rename = "type")]
+	pub message_type: MessageType,
+	/// The nonce the message was sent with, echoed back by hiven. See
+	/// [`MessageBuilder::nonce`](crate::client::MessageBuilder::nonce).
+	#[serde(default)]
+	pub nonce: Option<String>,
+	/// The id of the message this is a reply to.
+	#[serde(default)]
+	pub reply_to: Option<MessageId>,
+	/// The message this is a reply to, if hiven sent it along.
+	#[serde(default)]
+	pub referenced_message: Option<Box<Message>>,
+	/// Users mentioned in the message.
+	#[serde(default)]
+	pub mentions: Vec<User>,
+	/// Whether hiven flagged the message as mentioning everyone. See
+	/// [`Message::mentions_everyone`].
+	#[serde(default)]
+	pub mention_everyone: bool,
+	/// Ids of the roles mentioned in the message.
+	#[serde(default, deserialize_with = "from_str_seq")]
+	pub mention_roles: Vec<u64>,
+	#[serde(default)]
+	pub stickers: Vec<Sticker>,
+	#[serde(default)]
+	pub attachments: Vec<Attachment>,
+	/// Fields this version doesn't model yet, kept as they were sent. Only
+	/// available with the `preserve-unknown` feature.
+	#[cfg(feature = "preserve-unknown")]
+	#[serde(flatten)]
+	pub extra: HashMap<String, JSONValue>
+}
+
+impl Message {
+	/// When the message was sent, going by its id.
+	pub fn created_at(&self) -> SystemTime {
+		self.id.created_at()
+	}
+
+	/// Whether this message was sent by hiven, such as to announce a join or
+	/// a pin, rather than by a user.
+	pub fn is_system(&self) -> bool {
+		self.message_type.is_system()
+	}
+
+	/// Whether the message mentions the user the client is logged in as,
+	/// either in `mentions` or as `<@id>` in the content. Always false before
+	/// the gateway has logged in, as the user isn't known until then.
+	pub fn mentions_me(&self, client: &Client) -> bool {
+		match client.current_user_id() {
+			Some(user_id) => self.mentions.iter().any(|user| user.id == user_id)
+				|| self.content.contains(&format!("<@{}>", user_id)),
+			None => false
+		}
+	}
+
+	/// Whether the message mentions everyone, either flagged by hiven or as
+	/// `@everyone` in the content. Escaped mentions, like those from
+	/// [`escape_mentions`](crate::escape_mentions), don't count.
+	pub fn mentions_everyone(&self) -> bool {
+		self.mention_everyone || self.content.match_indices("@everyone").any(|(start, _)| {
+			let before = self.content[..start].chars().next_back();
+			let after = self.content[start + "@everyone".len()..].chars().next();
+			!before.is_some_and(char::is_alphanumeric)
+				&& !after.is_some_and(char::is_alphanumeric)
+		})
+	}
+
+	/// The content without formatting, and with user mentions replaced by the
+	/// user's name, for logs and text to speech. Mentions of users hiven didn't
+	/// send along in `mentions` are shown by id. Mentions in code are left as
+	/// written, like the rest of the code. See
+	/// [`strip_markdown`](crate::markdown::strip_markdown).
+	pub fn content_plain(&self) -> String {
+		strip_markdown_with(&self.content, &|user_id| Some(
+			match self.mentions.iter().find(|user| user.id == user_id) {
+				Some(user) => format!("@{}", user.name),
+				None => format!("@{}", user_id)
+			}))
+	}
+
+	/// Ids of the roles mentioned in the message, empty if there are none.
+	pub fn mentioned_roles(&self) -> &[u64] {
+		&self.mention_roles
+	}
+
+	/// Gets the message this is a reply to, fetching it if hiven only sent its
+	/// id. Returns `None` if this isn't a reply.
+	pub async fn resolve_reference(&self, client: &Client) ->
+			Result<Option<Message>, ClientError> {
+		match (&self.referenced_message, self.reply_to) {
+			(Some(message), _) => Ok(Some((**message).clone())),
+			(None, Some(message_id)) =>
+				Ok(Some(client.get_message(self.room_id, message_id).await?)),
+			(None, None) => Ok(None)
+		}
+	}
+
+	/// A link to the message in hiven's web client, for pointing people back
+	/// to it.
+	pub fn jump_url(&self) -> String {
+		format!("{}/messages/{}", room_url(self.house_id, self.room_id), self.id)
+	}
+}
+
+/// Messages are equal if they have the same id, even if one was edited since.
+impl PartialEq for Message {
+	fn eq(&self, other: &Self) -> bool {
+		self.id == other.id
+	}
+}
+
+impl Eq for Message {}
+
+impl Hash for Message {
+	fn hash<H>(&self, state: &mut H)
+			where H: Hasher {
+		self.id.hash(state)
+	}
+}
+
+impl PartialOrd for Message {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+/// Messages are ordered oldest first. Ids are snowflakes, which start with the
+/// time they were made, so ordering by id orders by when they were sent.
+impl Ord for Message {
+	fn cmp(&self, other: &Self) -> Ordering {
+		self.id.cmp(&other.id)
+	}
+}
+
+/// A file uploaded with a message.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Attachment {
+	pub filename: String,
+	/// Where the file can be downloaded from, usually hiven's media server.
+	#[serde(alias = "media_url")]
+	pub url: String,
+	/// The size of the file in bytes, if hiven sent it.
+	#[serde(default)]
+	pub size: Option<u64>
+}
+
+impl Attachment {
+	/// Downloads the whole file into memory. For large files, see
+	/// [`download_response`](Attachment::download_response).
+	pub async fn download(&self, client: &Client) -> Result<Vec<u8>, ClientError> {
+		Ok(self.download_response(client).await?.bytes().await?.to_vec())
+	}
+
+	/// Starts downloading the file, returning the response so the body can be
+	/// read a chunk at a time with [`HTTPResponse::chunk`].
+	///
+	/// The request goes through the client's http client, but without its
+	/// token, since the file could be hosted anywhere.
+	pub async fn download_response(&self, client: &Client) ->
+			Result<HTTPResponse, ClientError> {
+		client.fetch_url(&self.url).await
+	}
+}
+
+/// A sticker attached to a message.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Sticker {
+	pub name: String,
+	/// Where the sticker's image can be downloaded from, if hiven sent it.
+	#[serde(default)]
+	pub url: Option<String>,
 	#[serde(deserialize_with = "from_str")]
-	pub room_id: u64,
+	pub id: u64
+}
+
+/// A custom emoji in a house.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Emoji {
+	pub name: String,
+	/// Whether the emoji is a gif, rather than a still image.
+	#[serde(default, alias = "is_animated")]
+	pub animated: bool,
+	/// Where the emoji's image can be downloaded from, if hiven sent it.
+	#[serde(default)]
+	pub url: Option<String>,
 	#[serde(deserialize_with = "from_str")]
-	pub author_id: u64
+	pub id: u64
+}
+
+/// What kind of message a message is.
+///
+/// Types this version doesn't know about yet are kept as `Unknown`, so they
+/// don't break parsing. Types that aren't a number at all, like null, are
+/// taken as `Default`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MessageType {
+	/// A regular message, sent by a user.
+	#[default]
+	Default,
+	/// A message sent by hiven, such as a join or pin announcement.
+	System,
+	Unknown(u64)
+}
+
+impl MessageType {
+	/// Whether messages of this type weren't sent by a user. Unknown types are
+	/// assumed to be system messages, since new user message types are
+	/// unlikely.
+	pub fn is_system(self) -> bool {
+		!matches!(self, Self::Default)
+	}
+}
+
+impl From<u64> for MessageType {
+	fn from(message_type: u64) -> Self {
+		match message_type {
+			0 => Self::Default,
+			1 => Self::System,
+			message_type => Self::Unknown(message_type)
+		}
+	}
+}
+
+impl From<MessageType> for u64 {
+	fn from(message_type: MessageType) -> u64 {
+		match message_type {
+			MessageType::Default => 0,
+			MessageType::System => 1,
+			MessageType::Unknown(message_type) => message_type
+		}
+	}
+}
+
+impl<'d> Deserialize<'d> for MessageType {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+			where D: Deserializer<'d> {
+		// Numbers that come as strings, like room types, are still numbers.
+		Ok(match JSONValue::deserialize(deserializer)? {
+			JSONValue::Number(number) => number.as_u64().map_or(Self::Default, Self::from),
+			JSONValue::String(string) => string.parse::<u64>().map_or(Self::Default, Self::from),
+			_ => Self::Default
+		})
+	}
+}
+
+impl Serialize for MessageType {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+			where S: Serializer {
+		serializer.serialize_u64((*self).into())
+	}
+}
+
+/// Rich content shown with a message, such as a link preview. Fields that
+/// aren't set are left out when sending.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Embed {
+	/// What kind of embed this is, like `"rich"` or `"image"`, as hiven names
+	/// them.
+	#[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+	pub embed_type: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub title: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub description: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub url: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub image: Option<EmbedImage>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub color: Option<Color>
+}
+
+/// An image shown in an [`Embed`]. Hiven sends either just the url, or an
+/// object with the url and, sometimes, the image's size, and both are
+/// accepted.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "EmbedImageRepr")]
+pub struct EmbedImage {
+	pub url: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub width: Option<u32>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub height: Option<u32>
+}
+
+impl EmbedImage {
+	/// An image without a known size.
+	pub fn new<U>(url: U) -> Self
+			where U: Into<String> {
+		Self {url: url.into(), width: None, height: None}
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EmbedImageRepr {
+	Url(String),
+	Full {
+		url: String,
+		#[serde(default)]
+		width: Option<u32>,
+		#[serde(default)]
+		height: Option<u32>
+	}
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl From<EmbedImageRepr> for EmbedImage {
+	fn from(image: EmbedImageRepr) -> Self {
+		match image {
+			EmbedImageRepr::Url(url) => Self::new(url),
+			EmbedImageRepr::Full {url, width, height} => Self {url, width, height}
+		}
+	}
+}
+
+/// An RGB color, as used by roles and embeds.
+///
+/// Hiven represents colors as `"#RRGGBB"` strings, which is how they're
+/// serialized. Plain integers are accepted when deserializing too.
+///
+/// ```rust
+/// use hiven_rs::data::Color;
+///
+/// let color = Color::from_hex("#FF8000").unwrap();
+/// assert_eq!(color, Color::new(0xFF, 0x80, 0x00));
+/// assert_eq!(color.to_hex(), "#ff8000");
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color(pub u32);
+
+impl Color {
+	pub fn new(red: u8, green: u8, blue: u8) -> Self {
+		Self(u32::from(red) << 16 | u32::from(green) << 8 | u32::from(blue))
+	}
+
+	/// Parses a color from `"#RRGGBB"`. The `#` is optional.
+	pub fn from_hex(hex: &str) -> Result<Self, ParseColorError> {
+		let digits = hex.strip_prefix('#').unwrap_or(hex);
+
+		if digits.len() != 6 || !digits.chars().all(|digit| digit.is_ascii_hexdigit()) {
+			return Err(ParseColorError(hex.to_owned()))
+		}
+
+		// Can't fail, the digits were checked above.
+		Ok(Self(u32::from_str_radix(digits, 16).map_err(|_|
+			ParseColorError(hex.to_owned()))?))
+	}
+
+	/// Formats the color as `"#rrggbb"`. Bits above the lowest 24 aren't part
+	/// of the color, so they're left out.
+	pub fn to_hex(self) -> String {
+		format!("#{:06x}", self.0 & 0xFFFFFF)
+	}
+
+	pub fn red(self) -> u8 {
+		(self.0 >> 16) as u8
+	}
+
+	pub fn green(self) -> u8 {
+		(self.0 >> 8) as u8
+	}
+
+	pub fn blue(self) -> u8 {
+		self.0 as u8
+	}
+}
+
+impl FromStr for Color {
+	type Err = ParseColorError;
+
+	fn from_str(hex: &str) -> Result<Self, Self::Err> {
+		Self::from_hex(hex)
+	}
+}
+
+impl From<Color> for u32 {
+	fn from(color: Color) -> u32 {
+		color.0
+	}
+}
+
+impl<'d> Deserialize<'d> for Color {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+			where D: Deserializer<'d> {
+		deserializer.deserialize_any(ColorVisitor)
+	}
+}
+
+impl Serialize for Color {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+			where S: Serializer {
+		serializer.serialize_str(&self.to_hex())
+	}
+}
+
+struct ColorVisitor;
+
+impl<'d> Visitor<'d> for ColorVisitor {
+	type Value = Color;
+
+	fn expecting(&self, formatter: &mut Formatter) -> FMTResult {
+		write!(formatter, "a \"#RRGGBB\" string or an integer RGB color")
+	}
+
+	fn visit_str<E>(self, hex: &str) -> Result<Self::Value, E>
+			where E: DeserializeError {
+		Color::from_hex(hex).map_err(|_| E::invalid_value(Unexpected::Str(hex), &self))
+	}
+
+	fn visit_u64<E>(self, color: u64) -> Result<Self::Value, E>
+			where E: DeserializeError {
+		if color > 0xFFFFFF {
+			return Err(E::invalid_value(Unexpected::Unsigned(color), &self))
+		}
+
+		Ok(Color(color as u32))
+	}
+
+	fn visit_i64<E>(self, color: i64) -> Result<Self::Value, E>
+			where E: DeserializeError {
+		if color < 0 {
+			return Err(E::invalid_value(Unexpected::Signed(color), &self))
+		}
+
+		self.visit_u64(color as u64)
+	}
+}
+
+/// The error returned when a string isn't a valid `"#RRGGBB"` color.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseColorError(pub String);
+
+impl Display for ParseColorError {
+	fn fmt(&self, formatter: &mut Formatter) -> FMTResult {
+		write!(formatter, "{:?} is not a \"#RRGGBB\" hex color", self.0)
+	}
+}
+
+impl STDError for ParseColorError {}
+
+bitflags! {
+	/// Flags changing how a message is delivered.
+	///
+	/// Unknown bits sent by hiven are dropped on deserialization, so new flags
+	/// won't break message parsing.
+	#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+	pub struct MessageFlags: u32 {
+		/// Don't send notifications to anyone mentioned in the message.
+		const SILENT = 1 << 0;
+		/// The message is only visible for a limited time.
+		const EPHEMERAL = 1 << 1;
+	}
+}
+
+impl<'d> Deserialize<'d> for MessageFlags {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+			where D: Deserializer<'d> {
+		Ok(Self::from_bits_truncate(u32::deserialize(deserializer)?))
+	}
+}
+
+impl Serialize for MessageFlags {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+			where S: Serializer {
+		serializer.serialize_u32(self.bits())
+	}
+}
+
+bitflags! {
+	/// What a role or member is allowed to do.
+	///
+	/// Unlike [`MessageFlags`], unknown bits are kept, so permissions that
+	/// are read and then written back don't lose anything.
+	#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+	pub struct Permissions: u64 {
+		/// Every permission, whatever overrides say.
+		const ADMINISTRATOR = 1 << 0;
+		const VIEW_ROOMS = 1 << 1;
+		const SEND_MESSAGES = 1 << 2;
+		const MANAGE_MESSAGES = 1 << 3;
+		const ADD_REACTIONS = 1 << 4;
+		const ATTACH_FILES = 1 << 5;
+		const MENTION_EVERYONE = 1 << 6;
+		const KICK_MEMBERS = 1 << 7;
+		const BAN_MEMBERS = 1 << 8;
+		const MANAGE_ROOMS = 1 << 9;
+		const MANAGE_ROLES = 1 << 10;
+		const MANAGE_HOUSE = 1 << 11;
+	}
+}
+
+impl<'d> Deserialize<'d> for Permissions {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+			where D: Deserializer<'d> {
+		Ok(Self::from_bits_retain(u64::deserialize(deserializer)?))
+	}
+}
+
+impl Serialize for Permissions {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+			where S: Serializer {
+		serializer.serialize_u64(self.bits())
+	}
+}
+
+bitflags! {
+	/// Badges on a user's account.
+	///
+	/// Like [`Permissions`], unknown bits are kept. Hiven sends these as a
+	/// string at times, which is parsed too.
+	#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+	pub struct UserFlags: u64 {
+		/// Works on hiven.
+		const STAFF = 1 << 0;
+		const PARTNER = 1 << 1;
+		/// Supported hiven early on.
+		const EARLY_SUPPORTER = 1 << 2;
+		/// An automated account, rather than a person.
+		const BOT = 1 << 3;
+	}
+}
+
+impl<'d> Deserialize<'d> for UserFlags {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+			where D: Deserializer<'d> {
+		deserializer.deserialize_any(UserFlagsVisitor)
+	}
+}
+
+impl Serialize for UserFlags {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+			where S: Serializer {
+		serializer.serialize_u64(self.bits())
+	}
+}
+
+struct UserFlagsVisitor;
+
+impl<'d> Visitor<'d> for UserFlagsVisitor {
+	type Value = UserFlags;
+
+	fn expecting(&self, formatter: &mut Formatter) -> FMTResult {
+		write!(formatter, "user flags as an integer or a string of one")
+	}
+
+	fn visit_str<E>(self, flags: &str) -> Result<Self::Value, E>
+			where E: DeserializeError {
+		flags.parse().map(UserFlags::from_bits_retain)
+			.map_err(|_| E::invalid_value(Unexpected::Str(flags), &self))
+	}
+
+	fn visit_u64<E>(self, flags: u64) -> Result<Self::Value, E>
+			where E: DeserializeError {
+		Ok(UserFlags::from_bits_retain(flags))
+	}
+}
+
+/// Permissions allowed or denied to a role or member in one room, on top of
+/// what they have in the house.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PermissionOverride {
+	/// The id of the role or member this applies to.
+	#[serde(deserialize_with = "from_str")]
+	pub target_id: u64,
+	#[serde(rename = "type")]
+	pub target_type: OverrideTarget,
+	#[serde(default)]
+	pub allow: Permissions,
+	#[serde(default)]
+	pub deny: Permissions
+}
+
+/// What a [`PermissionOverride`] applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverrideTarget {
+	Role,
+	Member
+}
+
+/// Something a member did in a house, as recorded in its audit log.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AuditEntry {
+	#[serde(deserialize_with = "from_str")]
+	pub id: u64,
+	pub action_type: AuditAction,
+	/// The member who did it.
+	pub actor_id: UserId,
+	/// What it was done to, such as a banned member or an edited room.
+	#[serde(default, deserialize_with = "from_str_opt")]
+	pub target_id: Option<u64>,
+	#[serde(default)]
+	pub reason: Option<String>,
+	/// When it was done, in milliseconds since the unix epoch.
+	pub timestamp: u64
+}
+
+/// What kind of thing an [`AuditEntry`] records.
+///
+/// Actions this version doesn't know about yet are kept as `Unknown`, so they
+/// don't break parsing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AuditAction {
+	HouseUpdate,
+	RoomCreate,
+	RoomUpdate,
+	RoomDelete,
+	MemberKick,
+	MemberBan,
+	MemberUnban,
+	MemberUpdate,
+	RoleCreate,
+	RoleUpdate,
+	RoleDelete,
+	MessageDelete,
+	Unknown(u64)
+}
+
+impl From<u64> for AuditAction {
+	fn from(action: u64) -> Self {
+		match action {
+			1 => Self::HouseUpdate,
+			10 => Self::RoomCreate,
+			11 => Self::RoomUpdate,
+			12 => Self::RoomDelete,
+			20 => Self::MemberKick,
+			22 => Self::MemberBan,
+			23 => Self::MemberUnban,
+			24 => Self::MemberUpdate,
+			30 => Self::RoleCreate,
+			31 => Self::RoleUpdate,
+			32 => Self::RoleDelete,
+			72 => Self::MessageDelete,
+			action => Self::Unknown(action)
+		}
+	}
+}
+
+impl From<AuditAction> for u64 {
+	fn from(action: AuditAction) -> u64 {
+		match action {
+			AuditAction::HouseUpdate => 1,
+			AuditAction::RoomCreate => 10,
+			AuditAction::RoomUpdate => 11,
+			AuditAction::RoomDelete => 12,
+			AuditAction::MemberKick => 20,
+			AuditAction::MemberBan => 22,
+			AuditAction::MemberUnban => 23,
+			AuditAction::MemberUpdate => 24,
+			AuditAction::RoleCreate => 30,
+			AuditAction::RoleUpdate => 31,
+			AuditAction::RoleDelete => 32,
+			AuditAction::MessageDelete => 72,
+			AuditAction::Unknown(action) => action
+		}
+	}
+}
+
+impl<'d> Deserialize<'d> for AuditAction {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+			where D: Deserializer<'d> {
+		Ok(u64::deserialize(deserializer)?.into())
+	}
+}
+
+impl Serialize for AuditAction {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+			where S: Serializer {
+		serializer.serialize_u64((*self).into())
+	}
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct User {
 	pub username: String,
 	pub name: String,
 	pub icon: Option<String>,
 	pub header: Option<String>,
-	#[serde(deserialize_with = "from_str")]
-	pub id: u64
+	pub id: UserId,
+	/// The user's badges, empty if hiven didn't send any.
+	#[serde(default, alias = "user_flags")]
+	pub flags: UserFlags,
+	/// Fields this version doesn't model yet, kept as they were sent. Only
+	/// available with the `preserve-unknown` feature.
+	#[cfg(feature = "preserve-unknown")]
+	#[serde(flatten)]
+	pub extra: HashMap<String, JSONValue>
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl User {
+	/// Whether this is an automated account, handy for ignoring other bots.
+	pub fn is_bot(&self) -> bool {
+		self.flags.contains(UserFlags::BOT)
+	}
+
+	/// The full url of the user's avatar, if they have one.
+	pub fn avatar_url(&self) -> Option<String> {
+		media_url("users", self.id.into(), "icons", &self.icon)
+	}
+
+	/// The full url of the user's profile header, if they have one.
+	pub fn header_url(&self) -> Option<String> {
+		media_url("users", self.id.into(), "headers", &self.header)
+	}
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ClientSettings {
 	pub theme: Option<Theme>,
 	#[serde(rename = "enable_desktop_notifications")]
 	pub desktop_notifications: Option<bool>
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
 pub enum Presence {
+	#[default]
 	#[serde(rename = "offline")]
 	Offline,
 	#[serde(rename = "online")]
@@ -73,9 +1162,403 @@ pub enum Presence {
 	//?????
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum Theme {
 	#[serde(rename = "dark")]
 	Dark
 	//???????
 }
+
+#[cfg(test)]
+mod tests {
+	use super::{
+		AuditAction, AuditEntry, Color, Embed, EmbedImage, Emoji, House, HouseId, Message,
+		MessageId, MessageType, OverrideTarget, HIVEN_EPOCH, ParseColorError,
+		PermissionOverride, Permissions, RoomId, RoomType, Snowflake, User, UserFlags,
+		UserId
+	};
+	use serde_json::{
+		from_str as from_json, from_value, json, to_string as to_json, to_value
+	};
+	use crate::markdown::escape_mentions;
+	use std::time::{Duration, UNIX_EPOCH};
+
+	#[cfg(feature = "preserve-unknown")]
+	#[test]
+	fn preserve_unknown_test() {
+		let user: User = from_value(json!({
+			"username": "user", "name": "User", "icon": null, "header": null,
+			"id": "1", "bot": true
+		})).unwrap();
+
+		assert_eq!(user.extra["bot"], json!(true));
+		assert!(to_json(&user).unwrap().contains(r#""bot":true"#));
+	}
+
+	#[test]
+	fn color_hex_test() {
+		assert_eq!(Color::from_hex("#1e90ff"), Ok(Color(0x1E90FF)));
+		assert_eq!(Color::from_hex("1E90FF"), Ok(Color(0x1E90FF)));
+		assert_eq!(Color(0x1E90FF).to_hex(), "#1e90ff");
+		assert_eq!(Color(0x000001).to_hex(), "#000001");
+		assert_eq!(Color(0xFF1E90FF).to_hex(), "#1e90ff");
+		assert_eq!("#000000".parse(), Ok(Color(0)));
+	}
+
+	#[test]
+	fn color_malformed_test() {
+		for hex in ["", "#", "#12345", "#1234567", "#GGGGGG", "#+12345", "red"].iter() {
+			assert_eq!(Color::from_hex(hex), Err(ParseColorError(hex.to_string())));
+		}
+	}
+
+	#[test]
+	fn color_round_trip_test() {
+		let color = Color::new(0x12, 0xAB, 0xEF);
+		assert_eq!((color.red(), color.green(), color.blue()), (0x12, 0xAB, 0xEF));
+
+		let json = to_json(&color).unwrap();
+		assert_eq!(json, r##""#12abef""##);
+		assert_eq!(from_json::<Color>(&json).unwrap(), color);
+		assert_eq!(from_json::<Color>("1223663").unwrap(), color);
+		assert!(from_json::<Color>("16777216").is_err());
+		assert!(from_json::<Color>(r##""#12abeg""##).is_err());
+	}
+
+	#[test]
+	fn message_type_test() {
+		let message = |message_type: &str| from_json::<Message>(&format!(
+			r#"{{"content":"","id":"1","room_id":"2","author_id":"3"{}}}"#,
+			message_type)).unwrap();
+
+		assert_eq!(message("").message_type, MessageType::Default);
+		assert!(!message(r#","type":0"#).is_system());
+		assert!(message(r#","type":1"#).is_system());
+		assert_eq!(message(r#","type":9"#).message_type, MessageType::Unknown(9));
+		assert!(message(r#","type":9"#).is_system());
+		assert_eq!(message(r#","type":"1""#).message_type, MessageType::System);
+		assert_eq!(message(r#","type":null"#).message_type, MessageType::Default);
+		assert_eq!(message(r#","type":"system""#).message_type, MessageType::Default);
+	}
+
+	#[test]
+	fn room_type_test() {
+		let house = from_value::<House>(json!({"name": "A House", "icon": null,
+			"members": [], "id": "10", "owner_id": "1", "rooms": [
+				{"name": "general", "description": null, "position": 0, "id": "11"},
+				{"name": "lounge", "description": null, "position": 1, "type": 1,
+					"id": "12"},
+				{"name": "stage", "description": null, "position": 2, "type": 7,
+					"id": "13"},
+				{"name": "news", "description": null, "position": 3, "type": "1",
+					"id": "14"},
+				{"name": "feed", "description": null, "position": 4, "type": "feed",
+					"id": "15"},
+				{"name": "blank", "description": null, "position": 5, "type": null,
+					"id": "16"}
+			]})).unwrap();
+
+		let types: Vec<_> = house.rooms.iter().map(|room| room.room_type).collect();
+		assert_eq!(types, [RoomType::Text, RoomType::Portal, RoomType::Unknown(7),
+			RoomType::Portal, RoomType::Other, RoomType::Other]);
+		assert_eq!(to_value(&house.rooms[2]).unwrap()["type"], 7);
+		assert!(to_value(&house.rooms[4]).unwrap()["type"].is_null());
+	}
+
+	#[test]
+	fn media_url_test() {
+		let user = |icon: Option<&str>| from_value::<User>(json!({
+			"username": "user", "name": "User", "icon": icon, "header": null,
+			"id": "1"
+		})).unwrap();
+
+		assert_eq!(user(Some("a1b2c3.png")).avatar_url().unwrap(),
+			"https://media.hiven.io/v1/users/1/icons/a1b2c3.png");
+		assert_eq!(user(Some("https://example.com/a.png")).avatar_url().unwrap(),
+			"https://example.com/a.png");
+		assert!(user(Some("../etc")).avatar_url().is_none());
+		assert!(user(Some("")).avatar_url().is_none());
+		assert!(user(None).avatar_url().is_none());
+		assert!(user(None).header_url().is_none());
+	}
+
+	#[test]
+	fn user_flags_test() {
+		let user = |flags: &str| from_json::<User>(&format!(
+			r#"{{"username":"user","name":"User","icon":null,"header":null,"id":"1"{}}}"#,
+			flags)).unwrap();
+
+		assert!(user("").flags.is_empty());
+		assert!(!user("").is_bot());
+		assert!(user(r#","flags":8"#).is_bot());
+		assert_eq!(user(r#","user_flags":"5""#).flags,
+			UserFlags::STAFF | UserFlags::EARLY_SUPPORTER);
+		assert_eq!(user(r#","flags":64"#).flags.bits(), 1 << 6);
+	}
+
+	#[test]
+	fn member_count_test() {
+		let member = |id: &str, presence: &str| json!({"user": {"username": "user",
+			"name": "User", "icon": null, "header": null, "id": id},
+			"presence": presence});
+		let house: House = from_value(json!({
+			"name": "A House", "icon": null, "rooms": [], "id": "10", "owner_id": "1",
+			"members": [member("1", "online"), member("2", "offline"),
+				member("3", "online")]
+		})).unwrap();
+
+		assert_eq!(house.member_count(), 3);
+		assert_eq!(house.online_count(), 2);
+		// A borrowed house stands in for its id, so it's still usable after.
+		assert_eq!(HouseId::from(&house), 10);
+		assert_eq!(house.member_count(), 3);
+	}
+
+	#[test]
+	fn online_members_test() {
+		let house: House = from_value(json!({
+			"name": "A House", "icon": null, "rooms": [], "id": "10", "owner_id": "1",
+			"members": [
+				{"user": {"username": "one", "name": "One", "icon": null, "header": null,
+					"id": "1"}, "presence": "online"},
+				{"user": {"username": "two", "name": "Two", "icon": null, "header": null,
+					"id": "2"}, "presence": "offline"},
+				{"user": {"username": "three", "name": "Three", "icon": null,
+					"header": null, "id": "3"}}
+			]
+		})).unwrap();
+
+		let online: Vec<_> = house.online_members().iter().map(|member| member.user.id)
+			.collect();
+		assert_eq!(online, [1]);
+		assert_eq!(house.is_online(1), Some(true));
+		assert_eq!(house.is_online(2), Some(false));
+		// Not known isn't the same as offline.
+		assert_eq!(house.is_online(3), None);
+		assert_eq!(house.is_online(4), None);
+	}
+
+	#[test]
+	fn embed_round_trip_test() {
+		let embed = json!({"type": "rich", "title": "Release", "description": "v1.0 is out",
+			"url": "https://example.com/release", "color": "#ff8000",
+			"image": {"url": "https://example.com/banner.png", "width": 640, "height": 200}});
+		let message: Message = from_value(json!({"content": "", "id": "1", "room_id": "2",
+			"author_id": "3", "embed": embed})).unwrap();
+
+		let parsed = message.embed.unwrap();
+		assert_eq!(parsed.embed_type.as_deref(), Some("rich"));
+		assert_eq!(parsed.image.as_ref().unwrap().width, Some(640));
+		assert_eq!(to_value(&parsed).unwrap(), embed);
+		assert_eq!(from_value::<Embed>(to_value(&parsed).unwrap()).unwrap(), parsed);
+
+		// A bare url for the image works too, and unset fields are left out.
+		let embed: Embed = from_value(json!({"image": "https://example.com/cat.png"})).unwrap();
+		assert_eq!(embed.image, Some(EmbedImage::new("https://example.com/cat.png")));
+		assert_eq!(to_value(&embed).unwrap(),
+			json!({"image": {"url": "https://example.com/cat.png"}}));
+	}
+
+	#[test]
+	fn snowflake_test() {
+		let id: Snowflake = from_value(json!("175036227875828737")).unwrap();
+		assert_eq!(id, Snowflake(175036227875828737));
+		assert_eq!(from_value::<Snowflake>(json!(175036227875828737_u64)).unwrap(), id);
+		assert!(from_value::<Snowflake>(json!("not an id")).is_err());
+		assert_eq!(to_json(&id).unwrap(), r#""175036227875828737""#);
+
+		// 1000 milliseconds after the epoch, made by worker 5.
+		let id = Snowflake((1000 << 22) | 5);
+		assert_eq!(id.timestamp(), HIVEN_EPOCH + 1000);
+		assert_eq!(id.created_at(), UNIX_EPOCH + Duration::from_millis(HIVEN_EPOCH + 1000));
+
+		let message: Message = from_value(json!({"content": "hi", "id": id.to_string(),
+			"room_id": "2", "author_id": "3"})).unwrap();
+		assert_eq!(message.id, u64::from(id));
+		assert_eq!(message.created_at(), id.created_at());
+	}
+
+	#[test]
+	fn typed_id_test() {
+		let message: Message = from_value(json!({"content": "hi",
+			"id": "175036227875828737", "room_id": 2, "house_id": "1", "author_id": "3"}))
+			.unwrap();
+		assert_eq!(message.id, MessageId(Snowflake(175036227875828737)));
+		assert_eq!(message.room_id, RoomId::from(2));
+		assert_eq!(message.house_id, Some(HouseId::from(1)));
+		assert_eq!(message.author_id, 3);
+		assert_eq!(message.author_id.to_string(), "3");
+		assert_eq!("3".parse::<UserId>().unwrap(), message.author_id);
+
+		// Ids are sent back the way hiven sends them, as strings.
+		assert_eq!(to_value(message.room_id).unwrap(), json!("2"));
+	}
+
+	#[test]
+	fn message_order_test() {
+		// Snowflakes hold the time in milliseconds above the lower 22 bits.
+		let message = |time: u64, worker: u64, content: &str| from_value::<Message>(json!({
+			"content": content, "id": ((time << 22) | worker).to_string(),
+			"room_id": "2", "author_id": "3"
+		})).unwrap();
+
+		let mut messages = [message(3000, 0, "third"), message(1000, 5, "first"),
+			message(2000, 1, "second")];
+		messages.sort();
+		let contents: Vec<_> = messages.iter().map(|message| message.content.as_str())
+			.collect();
+		assert_eq!(contents, vec!["first", "second", "third"]);
+
+		assert_eq!(message(1000, 5, "first"), message(1000, 5, "edited"));
+		assert_ne!(message(1000, 5, "first"), message(1000, 6, "first"));
+		assert!(message(1000, 9, "") < message(1001, 0, ""));
+	}
+
+	#[test]
+	fn emoji_test() {
+		let emoji: Emoji = from_value(json!({"name": "party", "id": "5",
+			"animated": true})).unwrap();
+		assert!(emoji.animated);
+		assert!(emoji.url.is_none());
+
+		let emoji: Emoji = from_value(json!({"name": "blob", "id": "6"})).unwrap();
+		assert!(!emoji.animated);
+	}
+
+	#[test]
+	fn categories_test() {
+		let room = |id: &str, parent_id: Option<&str>| json!({"name": "room",
+			"description": null, "position": 0, "id": id, "parent_entity_id": parent_id});
+		let house: House = from_value(json!({
+			"name": "A House", "icon": null, "members": [], "id": "10", "owner_id": "1",
+			"entities": [{"name": "Text", "position": 0, "id": "20"}],
+			"rooms": [room("11", Some("20")), room("12", None), room("13", Some("20"))]
+		})).unwrap();
+
+		assert_eq!(house.categories[0].name, "Text");
+		let ids: Vec<u64> = house.rooms_in(20).map(|room| room.id.into()).collect();
+		assert_eq!(ids, vec![11, 13]);
+		assert!(house.rooms[1].parent_id.is_none());
+		assert_eq!(house.rooms[0].slowmode, 0);
+	}
+
+	#[test]
+	fn reply_test() {
+		let message: Message = from_json(r#"{"content":"yes","id":"5","room_id":"2",
+			"author_id":"3","reply_to":"4","referenced_message":{"content":"ok?",
+			"id":"4","room_id":"2","author_id":"1"}}"#).unwrap();
+
+		assert_eq!(message.reply_to, Some(4.into()));
+		assert_eq!(message.referenced_message.unwrap().content, "ok?");
+	}
+
+	#[test]
+	fn mentions_test() {
+		let message: Message = from_json(r#"{"content":"hey <@7>","id":"5",
+			"room_id":"2","author_id":"3","mentions":[{"username":"seven",
+			"name":"Seven","icon":null,"header":null,"id":"7"}]}"#).unwrap();
+
+		assert_eq!(message.mentions[0].id, 7);
+		assert!(!message.mentions_everyone());
+		assert!(message.mentioned_roles().is_empty());
+	}
+
+	#[test]
+	fn content_plain_test() {
+		let message = from_value::<Message>(json!({
+			"content": "**hey** <@7>, ask <@8> about [this](https://hiven.io) <@nope>",
+			"id": "5", "room_id": "2", "author_id": "3", "mentions": [{"username": "seven",
+				"name": "Seven", "icon": null, "header": null, "id": "7"}]
+		})).unwrap();
+
+		assert_eq!(message.content_plain(),
+			"hey @Seven, ask @8 about this (https://hiven.io) <@nope>");
+
+		let message = from_value::<Message>(json!({
+			"content": "`<@7>` and <@7>", "id": "5", "room_id": "2", "author_id": "3",
+			"mentions": [{"username": "seven", "name": "Seven", "icon": null,
+				"header": null, "id": "7"}]
+		})).unwrap();
+		assert_eq!(message.content_plain(), "<@7> and @Seven");
+	}
+
+	#[test]
+	fn mass_mentions_test() {
+		let message: Message = from_json(r#"{"content":"look","id":"5","room_id":"2",
+			"author_id":"3","mention_everyone":true,"mention_roles":["8","9"]}"#).unwrap();
+		assert!(message.mentions_everyone());
+		assert_eq!(message.mentioned_roles(), [8, 9]);
+
+		let message = |content: &str| from_value::<Message>(json!({"content": content,
+			"id": "5", "room_id": "2", "author_id": "3"})).unwrap().mentions_everyone();
+		assert!(message("@everyone look"));
+		assert!(message("look, @everyone!"));
+		assert!(!message(&escape_mentions("@everyone look")));
+		assert!(!message("me@everyone.com"));
+		assert!(!message("@everyones"));
+	}
+
+	#[test]
+	fn stickers_test() {
+		let message: Message = from_json(r#"{"content":"","id":"5","room_id":"2",
+			"author_id":"3","stickers":[{"name":"wave","id":"9"}]}"#).unwrap();
+
+		assert_eq!(message.stickers[0].name, "wave");
+		assert_eq!(message.stickers[0].id, 9);
+		assert!(message.stickers[0].url.is_none());
+	}
+
+	#[test]
+	fn attachments_test() {
+		let message: Message = from_json(r#"{"content":"","id":"5","room_id":"2",
+			"author_id":"3","attachments":[{"filename":"a.png",
+			"media_url":"https://media.hiven.io/v1/attachments/a.png","size":42}]}"#)
+			.unwrap();
+
+		assert_eq!(message.attachments[0].filename, "a.png");
+		assert_eq!(message.attachments[0].url,
+			"https://media.hiven.io/v1/attachments/a.png");
+		assert_eq!(message.attachments[0].size, Some(42));
+		assert!(from_json::<Message>(r#"{"content":"","id":"5","room_id":"2",
+			"author_id":"3"}"#).unwrap().attachments.is_empty());
+	}
+
+	#[test]
+	fn permission_override_test() {
+		let permission_override: PermissionOverride = from_json(r#"{"target_id":"5",
+			"type":"role","allow":6,"deny":32768}"#).unwrap();
+
+		assert_eq!(permission_override.target_type, OverrideTarget::Role);
+		assert_eq!(permission_override.allow,
+			Permissions::VIEW_ROOMS | Permissions::SEND_MESSAGES);
+		// Bits this version doesn't know about are kept.
+		assert_eq!(permission_override.deny.bits(), 1 << 15);
+	}
+
+	#[test]
+	fn audit_entry_test() {
+		let entry: AuditEntry = from_json(r#"{"id":"9","action_type":22,
+			"actor_id":"1","target_id":"2","reason":"Spam",
+			"timestamp":1600000000000}"#).unwrap();
+
+		assert_eq!(entry.action_type, AuditAction::MemberBan);
+		assert_eq!((entry.actor_id, entry.target_id), (1.into(), Some(2)));
+		assert_eq!(entry.reason.as_deref(), Some("Spam"));
+
+		let entry: AuditEntry = from_json(r#"{"id":"9","action_type":99,
+			"actor_id":"1","timestamp":1600000000000}"#).unwrap();
+		assert_eq!(entry.action_type, AuditAction::Unknown(99));
+		assert!(entry.target_id.is_none());
+	}
+
+	#[test]
+	fn jump_url_test() {
+		let message = |house_id: &str| from_json::<Message>(&format!(
+			r#"{{"content":"","id":"1","room_id":"2","author_id":"3"{}}}"#,
+			house_id)).unwrap();
+
+		assert_eq!(message(r#","house_id":"4""#).jump_url(),
+			"https://hiven.io/houses/4/rooms/2/messages/1");
+		assert_eq!(message("").jump_url(), "https://hiven.io/rooms/2/messages/1");
+	}
+}
@@ -1,18 +1,43 @@
-use self::super::{data::{ClientSettings, House, Message, User}, util::from_str};
+use self::super::{
+	data::{
+		ClientSettings, House, HouseId, Message, MessageId, Presence, PrivateRoom, Room,
+		RoomId, User, UserId
+	}
+};
 use serde::{
 	Deserialize, Serialize,
-	de::{Deserializer, Error as DeserializeError, MapAccess, Unexpected, Visitor},
-	ser::{SerializeMap, Serializer}
+	de::{
+		DeserializeSeed, Deserializer, Error as DeserializeError, MapAccess,
+		Unexpected, Visitor
+	},
+	ser::{Error as SerializeError, SerializeMap, Serializer}
 };
-use serde_value::Value as UndeserializedAny;
-use std::fmt::{Formatter, Result as FMTResult};
+use serde_json::{Value as JSONValue, json};
+use serde_value::{Value as UndeserializedAny, ValueDeserializer};
+use std::{
+	fmt::{Formatter, Result as FMTResult},
+	time::{Duration, SystemTime, UNIX_EPOCH}
+};
+use tokio::time::{Delay, delay_for};
+
+/// Re-exported for [`EventHandler::on_disconnect`](crate::EventHandler::on_disconnect).
+pub use async_tungstenite::tungstenite::protocol::frame::{CloseFrame, coding::CloseCode};
 
+// Events are much bigger than the other frames, but nearly every frame is an
+// event, so boxing them wouldn't save anything.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Frame {
 	Event(OpCodeEvent),
 	Hello(OpCodeHello),
 	Login(OpCodeLogin),
-	HeartBeat
+	HeartBeat,
+	/// Any other frame, sent as is. See [`OutgoingFrame`].
+	Custom {
+		op_code: u8,
+		data: JSONValue
+	}
 }
 
 impl<'d> Deserialize<'d> for Frame {
@@ -26,9 +51,7 @@ impl Serialize for Frame {
 	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
 			where S: Serializer {
 		match self {
-			Self::Event(_) => {
-				unimplemented!("C")
-			},
+			Self::Event(_) => Err(SerializeError::custom("event frames can't be sent")),
 			Self::Hello(op_code) => {
 				let mut map = serializer.serialize_map(Some(2))?;
 				map.serialize_entry("op", &1)?;
@@ -45,11 +68,60 @@ impl Serialize for Frame {
 				let mut map = serializer.serialize_map(Some(1))?;
 				map.serialize_entry("op", &3)?;
 				map.end()
+			},
+			Self::Custom {op_code, data} => {
+				let mut map = serializer.serialize_map(Some(2))?;
+				map.serialize_entry("op", op_code)?;
+				map.serialize_entry("d", data)?;
+				map.end()
 			}
 		}
 	}
 }
 
+/// A frame the client can send to hiven while connected, see
+/// [`Client::gateway_send`](crate::Client::gateway_send).
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum OutgoingFrame {
+	/// Sets the user's presence.
+	Presence(Presence),
+	/// Shows the user as typing in a room.
+	Typing {
+		room_id: RoomId
+	},
+	/// A frame this version doesn't know about, with its op code and data.
+	Custom {
+		op_code: u8,
+		data: JSONValue
+	}
+}
+
+// Op codes of frames only the client sends.
+const OP_PRESENCE: u8 = 4;
+const OP_TYPING: u8 = 5;
+
+/// The close code hiven closes the gateway with when it rejects the token
+/// sent to log in.
+pub const AUTH_FAILED_CLOSE_CODE: u16 = 4001;
+
+impl From<OutgoingFrame> for Frame {
+	fn from(frame: OutgoingFrame) -> Self {
+		match frame {
+			OutgoingFrame::Presence(presence) => Self::Custom {
+				op_code: OP_PRESENCE,
+				data: json!({"presence": presence})
+			},
+			// Ids are sent as strings, like hiven sends them.
+			OutgoingFrame::Typing {room_id} => Self::Custom {
+				op_code: OP_TYPING,
+				data: json!({"room_id": room_id.to_string()})
+			},
+			OutgoingFrame::Custom {op_code, data} => Self::Custom {op_code, data}
+		}
+	}
+}
+
 struct FrameVisitor;
 
 impl<'d> Visitor<'d> for FrameVisitor {
@@ -59,240 +131,458 @@ impl<'d> Visitor<'d> for FrameVisitor {
 		write!(formatter, "a hiven.io gateway websocket frame")
 	}
 
-	// This function is quite a bit of spaghetti code, but it should
-	// deserialize all hiven opcodes. To aid in reading, I've plastered comments
-	// everywhere, which should (hopefully) make it easier to understand.
+	// Hiven doesn't promise any ordering of "op", "e" and "d", so "d" can only
+	// be deserialized directly once we know what it holds. If it shows up
+	// earlier than that, it's kept as an UndeserializedAny and deserialized
+	// once the whole frame has been read.
 	// Yes, I would have used derive, except derive can't deserialize a data
 	// structure like this.
 	fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
 			where A: MapAccess<'d> {
-		let mut event: Option<&'d str> = None;
 		let mut op_code: Option<u8> = None;
+		let mut event: Option<String> = None;
 		let mut data: Option<UndeserializedAny> = None;
-		const FIELDS: [&'static str; 4] = ["op", "d", "e", "seq"];
+		let mut result: Option<Frame> = None;
+		const FIELDS: [&str; 4] = ["op", "d", "e", "seq"];
 
 		// Iterate over key values...
-		while let Some(key) = map.next_key()? {match key {
+		while let Some(key) = map.next_key::<String>()? {match key.as_str() {
 			// Ignore sequence, for now...
 			"seq" => {map.next_value::<UndeserializedAny>()?;},
-			"op" => {
-				op_code = Some(map.next_value()?);
-				break
-			},
-			"e" => if let None = event {event = Some(map.next_value()?)}
+			"op" => if op_code.is_none() {op_code = Some(map.next_value()?)}
+				else {Err(DeserializeError::duplicate_field("op"))?},
+			"e" => if event.is_none() {event = Some(map.next_value()?)}
 				else {Err(DeserializeError::duplicate_field("e"))?},
-			"d" => {
-				data = Some(map.next_value()?);
-				break
-			},
-			_ => Err(A::Error::unknown_field(key, &FIELDS))?
+			"d" => if result.is_none() && data.is_none() {match (op_code, &event) {
+				// OpCode deserialization...
+
+				// OpCodeEvent, with "e" found before "d"...
+				(Some(0), Some(event)) => result = Some(Frame::Event(
+					map.next_value_seed(EventSeed(event))?)),
+				// OpCodeHello...
+				(Some(1), _) => result = Some(Frame::Hello(map.next_value()?)),
+				// OpCodeLogin...
+				(Some(2), _) => result = Some(Frame::Login(map.next_value()?)),
+				// We don't know what "d" is yet, we must use UndeserializedAny.
+				_ => data = Some(map.next_value()?)
+			}} else {Err(DeserializeError::duplicate_field("d"))?},
+			key => Err(DeserializeError::unknown_field(key, &FIELDS))?
 		}}
 
-		if op_code.is_some() && data.is_none() {
-			// "op" was found before "d". No need to use UndeserializedAny.
-			let op_code = op_code.unwrap();
-			let mut result: Option<Frame> = None;
-
-			// Iterate over key values...
-			while let Some(key) = map.next_key()? {match key {
-				// Ignore sequence, for now...
-				"seq" => {map.next_value::<UndeserializedAny>()?;},
-				"op" => Err(DeserializeError::duplicate_field("op"))?,
-				"e" => if let None = event {event = Some(map.next_value()?)}
-					else {Err(DeserializeError::duplicate_field("e"))?},
-				"d" => if result.is_none() && data.is_none() {match op_code {
-					// OpCode deserialization...
-
-					// OpCodeEvent...
-					0 => if let None = event {
-						data = Some(map.next_value()?);
-						break
-					} else {result = Some(Frame::Event(match event.unwrap() {
-						// "op" was 0 and "e" was found before "d".
-						// No need to use UndeserializedAny.
-						// Event deserialization...
-
-						// EventInitState...
-						"INIT_STATE" => OpCodeEvent::InitState(map.next_value()?),
-						// EventHouseJoin...
-						"HOUSE_JOIN" => OpCodeEvent::HouseJoin(map.next_value()?),
-						// EventTypingStart...
-						"TYPING_START" => OpCodeEvent::TypingStart(map.next_value()?),
-						// EventMessageCreate...
-						"MESSAGE_CREATE" => OpCodeEvent::MessageCreate(map.next_value()?),
-
-						// Invalid event...
-						event @ _ => Err(DeserializeError::invalid_value(
-							Unexpected::Str(event), &"valid event"))?
-					}))},
-					// OpCodeHello...
-					1 => result = Some(Frame::Hello(map.next_value()?)),
-					// OpCodeLogin...
-					2 => result = Some(Frame::Login(map.next_value()?)),
-
-					// Operation codes that don't have data...
-					3 => Err(DeserializeError::unknown_field("d", &[]))?,
-					// Unknown operation code...
-					_ => Err(DeserializeError::invalid_value(
-						Unexpected::Unsigned(op_code.into()), &"valid opcode"))?
-				}} else {Err(DeserializeError::duplicate_field("d"))?},
-				_ => Err(A::Error::unknown_field(key, &[]))?
-			}}
-
-			if result.is_none() && data.is_some() {
-				// "op" was 0 and "d" was found before "e".
-				// We must use UndeserializedAny.
-				let event = event.unwrap();
-				let result: OpCodeEvent = match event {
-					// Event deserialization...
-
-					// EventInitState...
-					"INIT_STATE" => OpCodeEvent::InitState(map.next_value()?),
-					// EventHouseJoin...
-					"HOUSE_JOIN" => OpCodeEvent::HouseJoin(map.next_value()?),
-					// EventTypingStart...
-					"TYPING_START" => OpCodeEvent::TypingStart(map.next_value()?),
-					// EventMessageCreate...
-					"MESSAGE_CREATE" => OpCodeEvent::MessageCreate(map.next_value()?),
-
-					// Invalid event...
-					event @ _ => Err(DeserializeError::invalid_value(
-						Unexpected::Str(event), &"valid event"))?
-				};
-
-				Ok(Frame::Event(result))
-			} else {
-				// "op" was not 0.
-				Ok(match result {
-					Some(result) => result,
-					// Operation codes that don't have data...
-					None => match op_code {
-						// OpCode deserialization...
-
-						// OpCodeHeartBeat...
-						3 => Frame::HeartBeat,
-
-						// Operation codes that have data...
-						0 | 1 | 2 => Err(DeserializeError::missing_field("d"))?,
-						// Unknown operation code...
-						_ => Err(DeserializeError::invalid_value(
-							Unexpected::Unsigned(op_code.into()), &"valid opcode"))?
-					}
-				})
-			}
-		} else if op_code.is_none() && data.is_some() {
-			// "d" was found before "op". We must use UndeserializedAny.
-			let data = data.unwrap();
-			let mut result: Option<Frame> = None;
-			let mut op_zero = false; // If set to true, construct result in "e" arm.
-			// .clone may be removed when https://github.com/rust-lang/rfcs/pull/2593
-			// is pulled. (Enum variant types may give us a way to guard the moving of
-			// data statically.)
-			let data_into = || data.clone().deserialize_into()
-				.map_err(|err| err.into_error());
-
-			// Iterate over key values...
-			while let Some(key) = map.next_key()? {match key {
-				// Ignore sequence, for now...
-				"seq" => {map.next_value::<UndeserializedAny>()?;},
-				"op" => if result.is_none() && !op_zero {match map.next_value::<u8>()? {
-					// OpCode deserialization...
-
-					// OpCodeEvent...
-					0 => if let Some(event) = event {result = Some(Frame::Event(
-						match event {
-							// Event deserialization...
-
-							// EventInitState...
-							"INIT_STATE" => OpCodeEvent::InitState(map.next_value()?),
-							// EventHouseJoin...
-							"HOUSE_JOIN" => OpCodeEvent::HouseJoin(map.next_value()?),
-							// EventTypingStart...
-							"TYPING_START" => OpCodeEvent::TypingStart(map.next_value()?),
-							// EventMessageCreate...
-							"MESSAGE_CREATE" => OpCodeEvent::MessageCreate(map.next_value()?),
-
-							// Invalid event...
-							event @ _ => Err(DeserializeError::invalid_value(
-								Unexpected::Str(event), &"valid event"))?
-						}
-					))} else {op_zero = true},
-					// OpCodeHello...
-					1 => result = Some(Frame::Hello(data_into()?)),
-
-					// Operation codes that don't have data...
-					3 => Err(DeserializeError::unknown_field("d", &[]))?,
-					// Unknown operation code...
-					op_code @ _ => Err(DeserializeError::invalid_value(
-						Unexpected::Unsigned(op_code.into()), &"valid opcode"))?
-				}} else {Err(DeserializeError::duplicate_field("op"))?},
-				"e" => if op_zero {result = Some(Frame::Event(match map.next_value()? {
-					// Event deserialization...
-
-					// EventInitState...
-					"INIT_STATE" => OpCodeEvent::InitState(map.next_value()?),
-					// EventHouseJoin...
-					"HOUSE_JOIN" => OpCodeEvent::HouseJoin(map.next_value()?),
-					// EventTypingStart...
-					"TYPING_START" => OpCodeEvent::TypingStart(map.next_value()?),
-					// EventMessageCreate...
-					"MESSAGE_CREATE" => OpCodeEvent::MessageCreate(map.next_value()?),
-
-					// Invalid event...
-					event @ _ => Err(DeserializeError::invalid_value(
-						Unexpected::Str(event), &"valid event"))?
-				}))} else if let None = event {event = Some(map.next_value()?)}
-				else {Err(DeserializeError::duplicate_field("e"))?},
-				"d" => Err(DeserializeError::duplicate_field("d"))?,
-				_ => Err(A::Error::unknown_field(key, &[]))?
-			}}
-
-			result.ok_or(DeserializeError::missing_field(
-				if op_zero {"e"} else {"op"}))
-		} else {
-			Err(DeserializeError::missing_field("op"))
+		if let Some(result) = result {return Ok(result)}
+		let into_error = |err: serde_value::DeserializerError| A::Error::custom(err);
+
+		match op_code {
+			// OpCodeEvent...
+			Some(0) => match (event, data) {
+				(Some(event), Some(data)) => Ok(Frame::Event(EventSeed(&event)
+					.deserialize(ValueDeserializer::new(data)).map_err(into_error)?)),
+				(None, _) => Err(DeserializeError::missing_field("e")),
+				(_, None) => Err(DeserializeError::missing_field("d"))
+			},
+			// OpCodeHello and OpCodeLogin...
+			Some(op_code @ 1..=2) => match data {
+				Some(data) => Ok(match op_code {
+					1 => Frame::Hello(data.deserialize_into().map_err(into_error)?),
+					_ => Frame::Login(data.deserialize_into().map_err(into_error)?)
+				}),
+				None => Err(DeserializeError::missing_field("d"))
+			},
+			// OpCodeHeartBeat...
+			Some(3) => match data {
+				None | Some(UndeserializedAny::Unit) => Ok(Frame::HeartBeat),
+				Some(_) => Err(DeserializeError::unknown_field("d", &[]))
+			},
+			// Unknown operation code...
+			Some(op_code) => Err(DeserializeError::invalid_value(
+				Unexpected::Unsigned(op_code.into()), &"valid opcode")),
+			None => Err(DeserializeError::missing_field("op"))
 		}
 	}
 }
 
+/// Deserializes the "d" of an event frame, given the frame's "e".
+struct EventSeed<'e>(&'e str);
+
+impl<'d, 'e> DeserializeSeed<'d> for EventSeed<'e> {
+	type Value = OpCodeEvent;
+
+	fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+			where D: Deserializer<'d> {
+		Ok(match self.0 {
+			// Event deserialization...
+
+			// EventInitState...
+			"INIT_STATE" => OpCodeEvent::InitState(Deserialize::deserialize(deserializer)?),
+			// EventHouseJoin...
+			"HOUSE_JOIN" => OpCodeEvent::HouseJoin(Deserialize::deserialize(deserializer)?),
+			// EventHouseLeave...
+			"HOUSE_LEAVE" => OpCodeEvent::HouseLeave(Deserialize::deserialize(deserializer)?),
+			// EventHouseDelete...
+			"HOUSE_DELETE" => OpCodeEvent::HouseDelete(Deserialize::deserialize(deserializer)?),
+			// EventTypingStart...
+			"TYPING_START" => OpCodeEvent::TypingStart(Deserialize::deserialize(deserializer)?),
+			// EventMessageCreate...
+			"MESSAGE_CREATE" => OpCodeEvent::MessageCreate(Deserialize::deserialize(deserializer)?),
+			// EventMessagePin...
+			"MESSAGE_PIN" => OpCodeEvent::MessagePinned(Deserialize::deserialize(deserializer)?),
+			"MESSAGE_UNPIN" => OpCodeEvent::MessageUnpinned(Deserialize::deserialize(deserializer)?),
+			// EventPresenceUpdate...
+			"PRESENCE_UPDATE" =>
+				OpCodeEvent::PresenceUpdate(Deserialize::deserialize(deserializer)?),
+
+			// Invalid event...
+			event => Err(DeserializeError::invalid_value(
+				Unexpected::Str(event), &"valid event"))?
+		})
+	}
+}
+
 // Automatically serialized and deserialized by Frame.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum OpCodeEvent {
 	InitState(EventInitState),
 	HouseJoin(House),
+	HouseLeave(EventHouseLeave),
+	HouseDelete(EventHouseDelete),
 	TypingStart(EventTypingStart),
-	MessageCreate(Message)
+	MessageCreate(Message),
+	MessagePinned(EventMessagePin),
+	MessageUnpinned(EventMessagePin),
+	PresenceUpdate(EventPresenceUpdate)
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct OpCodeHello {
 	#[serde(rename = "hbt_int")]
 	pub heart_beat: u16
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct OpCodeLogin {
 	pub token: String
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// The state of the client, sent once after logging in.
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct EventInitState {
+	/// The user the client is logged in as.
 	pub user: User,
-	pub settings: ClientSettings
+	pub settings: ClientSettings,
+	/// Houses the user is in. Hiven usually sends these afterwards as separate
+	/// `HOUSE_JOIN` events, in which case this is empty and `house_ids` should
+	/// be used instead.
+	#[serde(default)]
+	pub houses: Vec<House>,
+	/// Ids of the houses the user is in.
+	#[serde(default)]
+	pub house_ids: Vec<HouseId>,
+	/// Direct message and group rooms the user is in.
+	#[serde(default)]
+	pub private_rooms: Vec<PrivateRoom>,
+	/// The id of this gateway session, if hiven provided one.
+	#[serde(default)]
+	pub session_id: Option<String>
+}
+
+impl EventInitState {
+	/// Every room in every house sent with the initial state. Private rooms
+	/// aren't included, they're in `private_rooms`.
+	pub fn all_rooms(&self) -> Vec<&Room> {
+		self.houses.iter().flat_map(|house| &house.rooms).collect()
+	}
+
+	/// Finds a room by its id, in any house sent with the initial state.
+	pub fn find_room<R>(&self, room: R) -> Option<&Room>
+			where R: Into<RoomId> {
+		let room_id = room.into();
+		self.houses.iter().flat_map(|house| &house.rooms)
+			.find(|room| room.id == room_id)
+	}
+}
+
+/// Sent when the client's user leaves, or is removed from, a house.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EventHouseLeave {
+	pub house_id: HouseId
+}
+
+/// Sent when a house the client's user is in gets deleted.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EventHouseDelete {
+	pub house_id: HouseId
+}
+
+/// Sent when a message is pinned or unpinned.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EventMessagePin {
+	pub message_id: MessageId,
+	pub room_id: RoomId,
+	/// The house the room is in, `None` for private rooms.
+	#[serde(default)]
+	pub house_id: Option<HouseId>,
+	/// The message itself, if hiven sent it along with the ids.
+	#[serde(default)]
+	pub message: Option<Message>
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Sent when a user goes online or offline.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EventPresenceUpdate {
+	#[serde(alias = "id")]
+	pub user_id: UserId,
+	pub presence: Presence,
+	/// The house the update is for, or `None` if it's for every house the user
+	/// shares with the client's user.
+	#[serde(default)]
+	pub house_id: Option<HouseId>
+}
+
+/// How long hiven shows someone as typing after a `TYPING_START`, unless they
+/// start typing again.
+pub const TYPING_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct EventTypingStart {
-	#[serde(deserialize_with = "from_str")]
-	pub room_id: u64,
-	#[serde(rename = "author_id", deserialize_with = "from_str")]
-	pub user_id: u64
+	pub room_id: RoomId,
+	/// The house the room is in, `None` for private rooms.
+	#[serde(default)]
+	pub house_id: Option<HouseId>,
+	#[serde(rename = "author_id")]
+	pub user_id: UserId,
+	/// When the user started typing, in milliseconds since the unix epoch, if
+	/// hiven sent it.
+	#[serde(default)]
+	pub timestamp: Option<u64>
+}
+
+impl EventTypingStart {
+	/// When the user stops being shown as typing, unless they send another
+	/// `TYPING_START` first. `None` if hiven didn't send a timestamp.
+	pub fn expires_at(&self) -> Option<SystemTime> {
+		Some(UNIX_EPOCH + Duration::from_millis(self.timestamp?) + TYPING_TIMEOUT)
+	}
+
+	/// Waits until the user is assumed to have stopped typing. Without a
+	/// timestamp, that's [`TYPING_TIMEOUT`] from now.
+	pub fn expired(&self) -> Delay {
+		let remaining = match self.expires_at() {
+			Some(expires_at) => expires_at.duration_since(SystemTime::now())
+				.unwrap_or_default(),
+			None => TYPING_TIMEOUT
+		};
+
+		delay_for(remaining)
+	}
+}
+
+/// How hiven recommends connecting to the gateway, from
+/// [`Client::gateway_bot_info`](crate::Client::gateway_bot_info).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GatewayBotInfo {
+	/// The websocket url to connect to.
+	pub url: String,
+	/// How many shards to split the connection into. 1 if hiven didn't say.
+	#[serde(default = "one")]
+	pub shards: u32,
+	/// How many more sessions may be started, if hiven limits it.
+	#[serde(default)]
+	pub session_start_limit: Option<SessionStartLimit>
+}
+
+/// How many gateway sessions may be started before the limit resets, see
+/// [`GatewayBotInfo`].
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SessionStartLimit {
+	pub total: u32,
+	pub remaining: u32,
+	/// Milliseconds until `remaining` is back to `total`.
+	pub reset_after: u64,
+	/// How many sessions may be started at once. 1 if hiven didn't say.
+	#[serde(default = "one")]
+	pub max_concurrency: u32
+}
+
+fn one() -> u32 {
+	1
 }
 
 #[cfg(test)]
 mod tests {
+	use super::{
+		EventInitState, EventTypingStart, Frame, GatewayBotInfo, OpCodeEvent, OutgoingFrame
+	};
+	use crate::data::Presence;
+	use serde_json::{from_str as from_json, from_value, json, to_value};
+	use std::time::{Duration, UNIX_EPOCH};
+	use tokio::time::timeout;
+
+	const INIT_STATE: &str = r#"{"op":0,"d":{"user":{"username":"hello_bot",
+		"user_flags":"0","name":"Hello Bot","id":"175036227875828737",
+		"icon":null,"header":null,"presence":"online"},"settings":{
+		"user_id":"175036227875828737","theme":"dark","room_overrides":{},
+		"onboarded":true,"enable_desktop_notifications":true},
+		"relationships":{},"read_state":{},"private_rooms":[{"type":1,
+		"recipients":[{"username":"someone","name":"Someone",
+		"id":"175036227875828738","icon":"a1b2c3.png","header":null}],
+		"owner_id":"175036227875828737","name":null,
+		"last_message_id":"175036227875828900","id":"175036227875828800",
+		"emoji":null,"description":null}],"presences":{},
+		"house_memberships":{},"house_ids":["175036227875828801",
+		"175036227875828802"]},"seq":1,"e":"INIT_STATE"}"#;
+
 	#[test]
 	fn serilization_test() {
 		
 	}
+
+	#[test]
+	fn init_state_test() {
+		let event = match from_json::<Frame>(INIT_STATE).unwrap() {
+			Frame::Event(OpCodeEvent::InitState(event)) => event,
+			frame => panic!("expected InitState, got {:?}", frame)
+		};
+
+		assert_eq!(event.user.username, "hello_bot");
+		assert_eq!(event.user.id, 175036227875828737);
+		assert_eq!(event.house_ids, vec![175036227875828801, 175036227875828802]);
+		assert!(event.houses.is_empty());
+		assert_eq!(event.private_rooms.len(), 1);
+		assert_eq!(event.private_rooms[0].id, 175036227875828800);
+		assert_eq!(event.private_rooms[0].last_message_id,
+			Some(175036227875828900.into()));
+		assert_eq!(event.private_rooms[0].recipients[0].username, "someone");
+		assert_eq!(event.session_id, None);
+	}
+
+	#[test]
+	fn init_state_event_first_test() {
+		let frame = r#"{"e":"INIT_STATE","op":0,"d":{"user":{"username":"a",
+			"name":"A","id":"1","icon":null,"header":null},"settings":{
+			"theme":null},"session_id":"abc"}}"#;
+
+		match from_json::<Frame>(frame).unwrap() {
+			Frame::Event(OpCodeEvent::InitState(event)) =>
+				assert_eq!(event.session_id.as_deref(), Some("abc")),
+			frame => panic!("expected InitState, got {:?}", frame)
+		}
+	}
+
+	#[test]
+	fn find_room_test() {
+		let room = |id: &str| json!({"name": id, "description": null,
+			"position": 0, "id": id});
+		let house = |id: &str, rooms| json!({"name": id, "icon": null,
+			"members": [], "rooms": rooms, "id": id, "owner_id": "1"});
+		let event: EventInitState = from_value(json!({
+			"user": {"username": "a", "name": "A", "id": "1", "icon": null,
+				"header": null},
+			"settings": {"theme": null},
+			"houses": [house("10", vec![room("11"), room("12")]),
+				house("20", vec![room("21")])]
+		})).unwrap();
+
+		let ids: Vec<_> = event.all_rooms().iter().map(|room| room.id).collect();
+		assert_eq!(ids, vec![11, 12, 21]);
+		assert_eq!(event.find_room(21).unwrap().name, "21");
+		assert!(event.find_room(10).is_none());
+	}
+
+	#[test]
+	fn typing_start_test() {
+		let frame = r#"{"op":0,"e":"TYPING_START","d":{"room_id":"2","house_id":"3",
+			"author_id":"1","timestamp":1600000000000}}"#;
+		let event = match from_json::<Frame>(frame).unwrap() {
+			Frame::Event(OpCodeEvent::TypingStart(event)) => event,
+			frame => panic!("expected TypingStart, got {:?}", frame)
+		};
+
+		assert_eq!(event.house_id, Some(3.into()));
+		assert_eq!(event.expires_at().unwrap(),
+			UNIX_EPOCH + Duration::from_secs(1600000010));
+	}
+
+	#[tokio::test]
+	async fn typing_expired_test() {
+		// Long expired, so this shouldn't wait.
+		let event: EventTypingStart = from_value(json!({
+			"room_id": "2", "author_id": "1", "timestamp": 0
+		})).unwrap();
+		timeout(Duration::from_secs(1), event.expired()).await.unwrap();
+	}
+
+	#[test]
+	fn gateway_bot_info_test() {
+		let info = from_value::<GatewayBotInfo>(json!({"url": "wss://swarm-dev.hiven.io",
+			"shards": 2, "session_start_limit": {"total": 1000, "remaining": 999,
+			"reset_after": 3600000}})).unwrap();
+		assert_eq!(info.shards, 2);
+		let limit = info.session_start_limit.unwrap();
+		assert_eq!((limit.remaining, limit.max_concurrency), (999, 1));
+
+		let info = from_value::<GatewayBotInfo>(json!({"url": "wss://swarm-dev.hiven.io"}))
+			.unwrap();
+		assert_eq!(info.shards, 1);
+		assert!(info.session_start_limit.is_none());
+	}
+
+	#[test]
+	fn outgoing_frame_test() {
+		let frame = |frame: OutgoingFrame| to_value(Frame::from(frame)).unwrap();
+
+		assert_eq!(frame(OutgoingFrame::Presence(Presence::Online)),
+			json!({"op": 4, "d": {"presence": "online"}}));
+		assert_eq!(frame(OutgoingFrame::Typing {room_id: 2.into()}),
+			json!({"op": 5, "d": {"room_id": "2"}}));
+		assert_eq!(frame(OutgoingFrame::Custom {op_code: 9, data: json!([1])}),
+			json!({"op": 9, "d": [1]}));
+	}
+
+	#[test]
+	fn event_frame_test() {
+		let frame = r#"{"op":0,"e":"TYPING_START","d":{"author_id":"1","room_id":"2","timestamp":0}}"#;
+		assert!(to_value(from_json::<Frame>(frame).unwrap()).is_err());
+	}
+
+	#[test]
+	fn message_pin_test() {
+		let frame = r#"{"op":0,"e":"MESSAGE_PIN","d":{"message_id":"5","room_id":"2"}}"#;
+		match from_json::<Frame>(frame).unwrap() {
+			Frame::Event(OpCodeEvent::MessagePinned(event)) => {
+				assert_eq!((event.message_id, event.room_id), (5.into(), 2.into()));
+				assert!(event.house_id.is_none() && event.message.is_none());
+			},
+			frame => panic!("expected MessagePinned, got {:?}", frame)
+		}
+
+		let frame = r#"{"op":0,"e":"MESSAGE_UNPIN","d":{"message_id":"5","room_id":"2",
+			"house_id":"3"}}"#;
+		match from_json::<Frame>(frame).unwrap() {
+			Frame::Event(OpCodeEvent::MessageUnpinned(event)) =>
+				assert_eq!(event.house_id, Some(3.into())),
+			frame => panic!("expected MessageUnpinned, got {:?}", frame)
+		}
+	}
+
+	#[test]
+	fn hello_test() {
+		match from_json::<Frame>(r#"{"d":{"hbt_int":30000},"op":1}"#).unwrap() {
+			Frame::Hello(hello) => assert_eq!(hello.heart_beat, 30000),
+			frame => panic!("expected Hello, got {:?}", frame)
+		}
+	}
+
+	#[test]
+	fn house_leave_test() {
+		match from_json::<Frame>(r#"{"op":0,"d":{"house_id":"42"},"e":"HOUSE_LEAVE"}"#).unwrap() {
+			Frame::Event(OpCodeEvent::HouseLeave(event)) => assert_eq!(event.house_id, 42),
+			frame => panic!("expected HouseLeave, got {:?}", frame)
+		}
+
+		match from_json::<Frame>(r#"{"e":"HOUSE_DELETE","op":0,"d":{"house_id":"42"}}"#).unwrap() {
+			Frame::Event(OpCodeEvent::HouseDelete(event)) => assert_eq!(event.house_id, 42),
+			frame => panic!("expected HouseDelete, got {:?}", frame)
+		}
+	}
 }
@@ -1,3 +1,10 @@
+use self::super::{
+	data::{
+		Embed, HouseId, MessageFlags, MessageId, OverrideTarget, Permissions, RoomId,
+		Snowflake, UserId
+	},
+	util::encode_path_segment
+};
 use reqwest::Method;
 use serde::{Deserialize, Serialize};
 
@@ -9,10 +16,90 @@ pub struct RequestInfo {
 
 pub enum PathInfo {
 	MessageSend {
-		channel_id: u64
+		channel_id: RoomId
+	},
+	MessageEdit {
+		channel_id: RoomId,
+		message_id: MessageId
+	},
+	MessageGet {
+		channel_id: RoomId,
+		message_id: MessageId
+	},
+	/// A page of messages, older than `before` if it's set.
+	MessageHistory {
+		channel_id: RoomId,
+		before: Option<MessageId>
+	},
+	/// All reactions on a message, or only those with `emoji` if it's set.
+	Reactions {
+		channel_id: RoomId,
+		message_id: MessageId,
+		emoji: Option<String>
+	},
+	/// A page of the users who reacted to a message with `emoji`, after the
+	/// user with the id `after` if it's set.
+	ReactionUsers {
+		channel_id: RoomId,
+		message_id: MessageId,
+		emoji: String,
+		after: Option<UserId>
+	},
+	/// The client's own reaction with `emoji` on a message.
+	OwnReaction {
+		channel_id: RoomId,
+		message_id: MessageId,
+		emoji: String
+	},
+	MemberBan {
+		house_id: HouseId,
+		user_id: UserId
+	},
+	/// A member of a house, or the client's own membership if `user_id` is
+	/// None.
+	Member {
+		house_id: HouseId,
+		user_id: Option<UserId>
+	},
+	House {
+		house_id: HouseId
+	},
+	/// The houses the client is in.
+	Houses,
+	/// How hiven recommends connecting to the gateway.
+	GatewayBot,
+	Roles {
+		house_id: HouseId
+	},
+	/// A house's emojis, or only the one with `emoji_id` if it's set.
+	Emojis {
+		house_id: HouseId,
+		emoji_id: Option<u64>
+	},
+	/// A house's categories, or only the one with `category_id` if it's set.
+	Categories {
+		house_id: HouseId,
+		category_id: Option<u64>
+	},
+	Room {
+		room_id: RoomId
+	},
+	/// The permission overrides of a room, or only the one for `target_id` if
+	/// it's set.
+	RoomPermissions {
+		room_id: RoomId,
+		target_id: Option<u64>
 	},
 	TypingTrigger {
-		channel_id: u64
+		channel_id: RoomId
+	},
+	/// A page of a house's audit log, filtered by whichever of the options are
+	/// set.
+	AuditLog {
+		house_id: HouseId,
+		action_type: Option<u64>,
+		actor_id: Option<UserId>,
+		before: Option<u64>
 	}
 }
 
@@ -21,11 +108,67 @@ impl PathInfo {
 		match self {
 			Self::MessageSend {channel_id} =>
 				format!("/rooms/{}/messages", channel_id),
-			/*Self::MessageEdit {channel_id, message_id, ..} |
-			Self::MessageDelete {channel_id, message_id, ..} =>
-				format!("/rooms/{}/messages/{}", channel_id, message_id)*/
+			Self::MessageEdit {channel_id, message_id} |
+			Self::MessageGet {channel_id, message_id} =>
+				format!("/rooms/{}/messages/{}", channel_id, message_id),
+			Self::MessageHistory {channel_id, before: None} =>
+				format!("/rooms/{}/messages", channel_id),
+			Self::MessageHistory {channel_id, before: Some(before)} =>
+				format!("/rooms/{}/messages?before={}", channel_id, before),
+			Self::Reactions {channel_id, message_id, emoji: None} =>
+				format!("/rooms/{}/messages/{}/reactions", channel_id, message_id),
+			Self::Reactions {channel_id, message_id, emoji: Some(emoji)} =>
+				format!("/rooms/{}/messages/{}/reactions/{}", channel_id, message_id,
+					encode_path_segment(emoji)),
+			Self::ReactionUsers {channel_id, message_id, emoji, after} => {
+				let path = format!("/rooms/{}/messages/{}/reactions/{}/users",
+					channel_id, message_id, encode_path_segment(emoji));
+				match after {
+					Some(after) => format!("{}?after={}", path, after),
+					None => path
+				}
+			},
+			Self::OwnReaction {channel_id, message_id, emoji} =>
+				format!("/rooms/{}/messages/{}/reactions/{}/@me", channel_id, message_id,
+					encode_path_segment(emoji)),
+			Self::MemberBan {house_id, user_id} =>
+				format!("/houses/{}/bans/{}", house_id, user_id),
+			Self::Member {house_id, user_id: Some(user_id)} =>
+				format!("/houses/{}/members/{}", house_id, user_id),
+			Self::Member {house_id, user_id: None} =>
+				format!("/houses/{}/members/@me", house_id),
+			Self::House {house_id} =>
+				format!("/houses/{}", house_id),
+			Self::Houses => "/users/@me/houses".to_owned(),
+			Self::GatewayBot => "/gateway/bot".to_owned(),
+			Self::Roles {house_id} =>
+				format!("/houses/{}/roles", house_id),
+			Self::Emojis {house_id, emoji_id: None} =>
+				format!("/houses/{}/emojis", house_id),
+			Self::Emojis {house_id, emoji_id: Some(emoji_id)} =>
+				format!("/houses/{}/emojis/{}", house_id, emoji_id),
+			Self::Categories {house_id, category_id: None} =>
+				format!("/houses/{}/entities", house_id),
+			Self::Categories {house_id, category_id: Some(category_id)} =>
+				format!("/houses/{}/entities/{}", house_id, category_id),
+			Self::Room {room_id} =>
+				format!("/rooms/{}", room_id),
+			Self::RoomPermissions {room_id, target_id: None} =>
+				format!("/rooms/{}/permissions", room_id),
+			Self::RoomPermissions {room_id, target_id: Some(target_id)} =>
+				format!("/rooms/{}/permissions/{}", room_id, target_id),
 			Self::TypingTrigger {channel_id} =>
-				format!("/rooms/{}/typing", channel_id)
+				format!("/rooms/{}/typing", channel_id),
+			Self::AuditLog {house_id, action_type, actor_id, before} => {
+				let query: Vec<String> = [("action_type", action_type),
+					("actor_id", &actor_id.map(u64::from)), ("before", before)].iter()
+					.filter_map(|(name, value)| value.map(|value|
+						format!("{}={}", name, value)))
+					.collect();
+
+				if query.is_empty() {format!("/houses/{}/audit-log", house_id)}
+				else {format!("/houses/{}/audit-log?{}", house_id, query.join("&"))}
+			}
 		}
 	}
 }
@@ -34,19 +177,110 @@ impl PathInfo {
 #[serde(untagged)]
 pub enum RequestBodyInfo {
 	MessageSend {
-		content: String
+		content: String,
+		#[serde(default, skip_serializing_if = "MessageFlags::is_empty")]
+		flags: MessageFlags,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		nonce: Option<String>,
+		// Ids are sent as strings, like hiven sends them.
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		sticker_id: Option<String>,
+		#[serde(default, skip_serializing_if = "Vec::is_empty")]
+		attachment_urls: Vec<String>,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		embed: Option<Embed>
+	},
+	// Fields that are None are left untouched by hiven.
+	MessageEdit {
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		content: Option<String>,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		embed: Option<Embed>
+	},
+	MemberBan {
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		reason: Option<String>,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		delete_message_days: Option<u8>
+	},
+	CategoryCreate {
+		name: String
+	},
+	CategoryEdit {
+		name: String
 	},
+	// The image is a data uri.
+	EmojiCreate {
+		name: String,
+		image: String
+	},
+	RoomEdit {
+		rate_limit_per_user: u32
+	},
+	RolesReorder(Vec<RolePosition>),
+	// A nick of None clears it, so it has to be sent.
+	MemberEdit {
+		nick: Option<String>
+	},
+	AuditLogGet {},
+	CategoryDelete {},
+	EmojiDelete {},
+	EmojisGet {},
+	GatewayBotGet {},
+	HouseGet {},
+	HouseLeave {},
+	HousesGet {},
+	MessageGet {},
+	MessageHistory {},
+	PermissionOverrideSet {
+		#[serde(rename = "type")]
+		target_type: OverrideTarget,
+		allow: Permissions,
+		deny: Permissions
+	},
+	ReactionAdd {},
+	ReactionRemove {},
+	ReactionsClear {},
+	ReactionUsersGet {},
+	RoomGet {},
+	RoomPermissionsGet {},
 	TypingTrigger {}
 }
 
 impl RequestBodyInfo {
 	pub fn method(&self) -> Method {
 		match self {
-			Self::MessageSend {..} | Self::TypingTrigger {} => Method::POST
+			Self::CategoryCreate {..} | Self::EmojiCreate {..} |
+				Self::MessageSend {..} | Self::TypingTrigger {} => Method::POST,
+			Self::CategoryEdit {..} | Self::MemberEdit {..} |
+				Self::MessageEdit {..} | Self::RolesReorder(_) |
+				Self::RoomEdit {..} => Method::PATCH,
+			Self::MemberBan {..} | Self::PermissionOverrideSet {..} |
+				Self::ReactionAdd {} => Method::PUT,
+			Self::CategoryDelete {} | Self::EmojiDelete {} | Self::HouseLeave {} |
+				Self::ReactionRemove {} | Self::ReactionsClear {} => Method::DELETE,
+			Self::AuditLogGet {} | Self::EmojisGet {} | Self::GatewayBotGet {} |
+				Self::HouseGet {} |
+				Self::HousesGet {} | Self::MessageGet {} |
+				Self::MessageHistory {} | Self::ReactionUsersGet {} | Self::RoomGet {} |
+				Self::RoomPermissionsGet {} => Method::GET
 		}
 	}
 }
 
+/// Where a role should be in a house's hierarchy.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RolePosition {
+	pub id: Snowflake,
+	pub position: u32
+}
+
+/// The envelope hiven wraps response data in.
+#[derive(Debug, Deserialize)]
+pub struct ResponseInfo<T> {
+	pub data: T
+}
+
 /*pub enum RequestInfo {
 	MessageSend {
 		channel_id: u64,
@@ -82,3 +316,162 @@ impl RequestInfo {
 		}
 	}
 }*/
+
+#[cfg(test)]
+mod tests {
+	use super::{PathInfo, RequestBodyInfo, RolePosition};
+	use crate::{data::{Embed, MessageFlags, Snowflake}, util::encode_image};
+	use serde_json::{json, to_value};
+
+	#[test]
+	fn message_send_body_test() {
+		let body = |nonce: Option<&str>| to_value(RequestBodyInfo::MessageSend {
+			content: "hi".to_owned(),
+			flags: MessageFlags::empty(),
+			nonce: nonce.map(str::to_owned),
+			sticker_id: None,
+			attachment_urls: Vec::new(),
+			embed: None
+		}).unwrap();
+
+		assert_eq!(body(None), json!({"content": "hi"}));
+		assert_eq!(body(Some("42")), json!({"content": "hi", "nonce": "42"}));
+		assert_eq!(to_value(RequestBodyInfo::MessageSend {
+			content: String::new(),
+			flags: MessageFlags::empty(),
+			nonce: None,
+			sticker_id: None,
+			attachment_urls: vec!["https://example.com/cat.png".to_owned()],
+			embed: None
+		}).unwrap(), json!({"content": "", "attachment_urls": ["https://example.com/cat.png"]}));
+	}
+
+	#[test]
+	fn emoji_create_body_test() {
+		let body = |image: &[u8]| to_value(RequestBodyInfo::EmojiCreate {
+			name: "blob".to_owned(),
+			image: encode_image(image)
+		}).unwrap();
+
+		assert_eq!(body(b"\x89PNG"), json!({"name": "blob",
+			"image": "data:image/png;base64,iVBORw=="}));
+		assert_eq!(body(b"GIF89a"), json!({"name": "blob",
+			"image": "data:image/gif;base64,R0lGODlh"}));
+		assert_eq!(body(b"ab"), json!({"name": "blob",
+			"image": "data:image/png;base64,YWI="}));
+	}
+
+	#[test]
+	fn roles_reorder_body_test() {
+		let body = RequestBodyInfo::RolesReorder(vec![
+			RolePosition {id: Snowflake(1), position: 0},
+			RolePosition {id: Snowflake(2), position: 1}
+		]);
+
+		assert_eq!(PathInfo::Roles {house_id: 3.into()}.path(), "/houses/3/roles");
+		assert_eq!(to_value(body).unwrap(),
+			json!([{"id": "1", "position": 0}, {"id": "2", "position": 1}]));
+	}
+
+	#[test]
+	fn message_edit_body_test() {
+		let embed = Embed {title: Some("Status".to_owned()), ..Embed::default()};
+		let body = |content: Option<&str>, embed: Option<Embed>| to_value(
+			RequestBodyInfo::MessageEdit {content: content.map(str::to_owned), embed})
+			.unwrap();
+
+		// Fields that aren't being changed must be left out, not sent as null.
+		assert_eq!(body(Some("hi"), None), json!({"content": "hi"}));
+		assert_eq!(body(None, Some(embed.clone())), json!({"embed": {"title": "Status"}}));
+		assert_eq!(body(Some("hi"), Some(embed)),
+			json!({"content": "hi", "embed": {"title": "Status"}}));
+		assert_eq!(body(None, None), json!({}));
+	}
+
+	#[test]
+	fn member_ban_body_test() {
+		let body = RequestBodyInfo::MemberBan {
+			reason: None,
+			delete_message_days: Some(1)
+		};
+
+		assert_eq!(to_value(&body).unwrap(), json!({"delete_message_days": 1}));
+		assert_eq!(body.method(), "PUT");
+	}
+
+	#[test]
+	fn member_edit_test() {
+		assert_eq!(PathInfo::Member {house_id: 1.into(), user_id: Some(2.into())}.path(),
+			"/houses/1/members/2");
+		assert_eq!(PathInfo::Member {house_id: 1.into(), user_id: None}.path(),
+			"/houses/1/members/@me");
+		assert_eq!(to_value(RequestBodyInfo::MemberEdit {nick: None}).unwrap(),
+			json!({"nick": null}));
+	}
+
+	#[test]
+	fn message_history_path_test() {
+		let path = |before| PathInfo::MessageHistory {
+			channel_id: 1.into(),
+			before
+		}.path();
+
+		assert_eq!(path(None), "/rooms/1/messages");
+		assert_eq!(path(Some(2.into())), "/rooms/1/messages?before=2");
+	}
+
+	#[test]
+	fn audit_log_path_test() {
+		let path = |action_type, actor_id, before| PathInfo::AuditLog {
+			house_id: 1.into(),
+			action_type,
+			actor_id,
+			before
+		}.path();
+
+		assert_eq!(path(None, None, None), "/houses/1/audit-log");
+		assert_eq!(path(Some(22), None, None), "/houses/1/audit-log?action_type=22");
+		assert_eq!(path(Some(22), Some(3.into()), Some(4)),
+			"/houses/1/audit-log?action_type=22&actor_id=3&before=4");
+	}
+
+	#[test]
+	fn categories_path_test() {
+		assert_eq!(PathInfo::Categories {house_id: 1.into(), category_id: None}.path(),
+			"/houses/1/entities");
+		assert_eq!(PathInfo::Categories {house_id: 1.into(), category_id: Some(2)}.path(),
+			"/houses/1/entities/2");
+	}
+
+	#[test]
+	fn reactions_path_test() {
+		let path = |emoji: Option<&str>| PathInfo::Reactions {
+			channel_id: 1.into(),
+			message_id: 2.into(),
+			emoji: emoji.map(str::to_owned)
+		}.path();
+
+		assert_eq!(path(None), "/rooms/1/messages/2/reactions");
+		assert_eq!(path(Some("👍")), "/rooms/1/messages/2/reactions/%F0%9F%91%8D");
+		assert_eq!(path(Some("blob:123")), "/rooms/1/messages/2/reactions/blob%3A123");
+		assert_eq!(PathInfo::OwnReaction {
+			channel_id: 1.into(),
+			message_id: 2.into(),
+			emoji: "👍".to_owned()
+		}.path(), "/rooms/1/messages/2/reactions/%F0%9F%91%8D/@me");
+	}
+
+	#[test]
+	fn reaction_users_path_test() {
+		let path = |after| PathInfo::ReactionUsers {
+			channel_id: 1.into(),
+			message_id: 2.into(),
+			emoji: "👍".to_owned(),
+			after
+		}.path();
+
+		assert_eq!(path(None), "/rooms/1/messages/2/reactions/%F0%9F%91%8D/users");
+		assert_eq!(path(Some(3.into())),
+			"/rooms/1/messages/2/reactions/%F0%9F%91%8D/users?after=3");
+	}
+}
@@ -1,8 +1,17 @@
-#![feature(future_readiness_fns, try_blocks)]
+#[cfg(feature = "cache")]
+pub mod cache;
 pub mod client;
+#[cfg(feature = "commands")]
+pub mod commands;
 pub mod data;
 pub mod gateway;
 pub mod http;
+pub mod markdown;
+#[cfg(any(test, feature = "mock-gateway"))]
+pub mod mock;
 mod util;
 
-pub use self::client::{Client, EventHandler, GateKeeper};
+pub use self::{
+	client::{Client, ClientBuilder, EventHandler, GateKeeper, GatewayExit},
+	markdown::{escape_markdown, escape_mentions}
+};
@@ -0,0 +1,355 @@
+//! Helpers for working with hiven's message formatting.
+//!
+//! These are useful when relaying text from untrusted sources, so it's shown
+//! exactly as written instead of being formatted or pinging people, or for
+//! going the other way and getting plain text out of a message.
+
+const MARKDOWN_CHARACTERS: [char; 7] = ['\\', '*', '_', '~', '`', '|', '>'];
+const ZERO_WIDTH_SPACE: char = '\u{200B}';
+
+/// Escapes every character hiven would treat as formatting, so the text is
+/// displayed as is.
+///
+/// ```rust
+/// assert_eq!(hiven_rs::escape_markdown("**hi**"), r"\*\*hi\*\*");
+/// ```
+pub fn escape_markdown(text: &str) -> String {
+	let mut escaped = String::with_capacity(text.len());
+
+	for character in text.chars() {
+		if MARKDOWN_CHARACTERS.contains(&character) {escaped.push('\\')}
+		escaped.push(character);
+	}
+
+	escaped
+}
+
+/// Breaks up anything that could be a mention, such as `@everyone` or
+/// `<@id>`, by putting a zero width space after each `@`. The text still looks
+/// the same when displayed.
+///
+/// ```rust
+/// assert_eq!(hiven_rs::escape_mentions("@everyone"), "@\u{200B}everyone");
+/// ```
+pub fn escape_mentions(text: &str) -> String {
+	let mut escaped = String::with_capacity(text.len());
+
+	for character in text.chars() {
+		escaped.push(character);
+		if character == '@' {escaped.push(ZERO_WIDTH_SPACE)}
+	}
+
+	escaped
+}
+
+/// Removes hiven's formatting, leaving roughly what a reader sees, for logs
+/// and text to speech. Code is kept as written and links keep their url after
+/// the text. This is best effort: markers within words, like in `snake_case`,
+/// are left alone.
+///
+/// ```rust
+/// use hiven_rs::markdown::strip_markdown;
+///
+/// assert_eq!(strip_markdown("**hi** `there`"), "hi there");
+/// ```
+pub fn strip_markdown(text: &str) -> String {
+	strip_markdown_with(text, &|_| None)
+}
+
+// Like strip_markdown, but also replaces user mentions outside of code with
+// whatever `mention` makes of the user's id, if anything.
+pub(crate) fn strip_markdown_with(text: &str, mention: &dyn Fn(u64) -> Option<String>) ->
+		String {
+	let characters: Vec<char> = text.chars().collect();
+	let mut plain = String::with_capacity(text.len());
+	let mut index = 0;
+
+	while index < characters.len() {
+		let character = characters[index];
+		let run = characters[index..].iter().take_while(|&&c| c == character).count();
+
+		if (index == 0 || characters[index - 1] == '\n')
+				&& characters[index..].starts_with(&['>', ' ']) {
+			index += 2;
+			continue
+		}
+
+		match character {
+			'\\' if characters.get(index + 1)
+					.is_some_and(|next| next.is_ascii_punctuation()) => {
+				plain.push(characters[index + 1]);
+				index += 2;
+			},
+			'`' => match find_run(&characters, index + run, '`', run) {
+				Some(end) => {
+					plain.extend(code(&characters[index + run..end], run >= 3));
+					index = end + run;
+				},
+				None => {
+					plain.extend(&characters[index..index + run]);
+					index += run;
+				}
+			},
+			'*' | '_' => {
+				let before = index.checked_sub(1).map(|before| characters[before]);
+				let after = characters.get(index + run);
+				// Within a word, like 2*3 or snake_case.
+				if before.is_some_and(char::is_alphanumeric)
+						&& after.is_some_and(|after| after.is_alphanumeric()) {
+					plain.extend(&characters[index..index + run]);
+				}
+				index += run;
+			},
+			'~' | '|' if run >= 2 => index += run,
+			'<' => match user_mention(&characters[index..])
+					.and_then(|(user_id, length)| Some((mention(user_id)?, length))) {
+				Some((name, length)) => {
+					plain.push_str(&name);
+					index += length;
+				},
+				None => {
+					plain.push(character);
+					index += 1;
+				}
+			},
+			'[' => match link(&characters[index..]) {
+				Some((label, url, length)) => {
+					let label: String = label.iter().collect();
+					plain.push_str(&strip_markdown_with(&label, mention));
+					plain.push_str(" (");
+					plain.extend(url);
+					plain.push(')');
+					index += length;
+				},
+				None => {
+					plain.push(character);
+					index += 1;
+				}
+			},
+			_ => {
+				plain.push(character);
+				index += 1;
+			}
+		}
+	}
+
+	plain
+}
+
+// Finds the next run of exactly `length` of a character, from `start`.
+fn find_run(characters: &[char], start: usize, character: char, length: usize) ->
+		Option<usize> {
+	let mut index = start;
+	while index < characters.len() {
+		let run = characters[index..].iter().take_while(|&&c| c == character).count();
+		if run == length {return Some(index)}
+		index += run.max(1);
+	}
+	None
+}
+
+// The inside of a code span or block, without the language of a block or the
+// line breaks around it.
+fn code(code: &[char], block: bool) -> impl Iterator<Item = &char> {
+	let mut code = code;
+	if block {
+		if let Some(newline) = code.iter().position(|&c| c == '\n') {
+			// The language is a single word right after the opening backticks.
+			if !code[..newline].iter().any(|c| c.is_whitespace()) {
+				code = &code[newline + 1..];
+			}
+		}
+		if code.last() == Some(&'\n') {code = &code[..code.len() - 1]}
+	}
+	code.iter()
+}
+
+// The user id in a `<@id>` mention at the start of the text, and how long the
+// mention is.
+fn user_mention(characters: &[char]) -> Option<(u64, usize)> {
+	let id = characters.strip_prefix(&['<', '@'])?;
+	let id_length = id.iter().position(|&c| c == '>')?;
+	let id: String = id[..id_length].iter().collect();
+	Some((id.parse().ok()?, id_length + 3))
+}
+
+// Splits a `[label](url)` link at the start of the text into its label and
+// url, and how long it is.
+fn link(characters: &[char]) -> Option<(&[char], &[char], usize)> {
+	let label_end = characters.iter().position(|&c| c == ']' || c == '\n')?;
+	if characters[label_end] != ']' || characters.get(label_end + 1) != Some(&'(') {
+		return None
+	}
+	let url_start = label_end + 2;
+	let url_length = characters[url_start..].iter()
+		.position(|&c| c == ')' || c.is_whitespace())?;
+	if characters[url_start + url_length] != ')' {return None}
+
+	Some((&characters[1..label_end], &characters[url_start..url_start + url_length],
+		url_start + url_length + 1))
+}
+
+/// Splits text into pieces of at most `max_length` characters, preferring to
+/// split between code blocks and lines, then between words, and only splitting
+/// within a word as a last resort. Whitespace at the end of each piece is
+/// trimmed.
+///
+/// ```rust
+/// use hiven_rs::markdown::split_message;
+///
+/// assert_eq!(split_message("one two three", 8), vec!["one two", "three"]);
+/// ```
+pub fn split_message(text: &str, max_length: usize) -> Vec<String> {
+	let mut pieces = Vec::new();
+	let mut piece = String::new();
+
+	split_into(blocks(text).into_iter(), 0, max_length, &mut pieces, &mut piece);
+	push_piece(&mut pieces, piece);
+	pieces
+}
+
+// Splits text into lines, except lines within a code block are kept together
+// with the rest of the block.
+fn blocks(text: &str) -> Vec<&str> {
+	let mut blocks = Vec::new();
+	let (mut start, mut end) = (0, 0);
+	let mut in_code = false;
+
+	for line in text.split_inclusive('\n') {
+		end += line.len();
+		if line.matches("```").count() % 2 == 1 {in_code = !in_code}
+		if !in_code {
+			blocks.push(&text[start..end]);
+			start = end;
+		}
+	}
+
+	// An unclosed code block.
+	if start < text.len() {blocks.push(&text[start..])}
+	blocks
+}
+
+// Packs parts into pieces. Parts that are too long are split further, blocks
+// into lines, lines into words, and words into characters.
+fn split_into<'t, I>(parts: I, depth: u8, max_length: usize,
+		pieces: &mut Vec<String>, piece: &mut String)
+			where I: Iterator<Item = &'t str> {
+	for part in parts {
+		let length = part.chars().count();
+		if piece.chars().count() + length <= max_length {
+			piece.push_str(part);
+			continue
+		}
+
+		push_piece(pieces, std::mem::take(piece));
+		if length <= max_length {piece.push_str(part)} else {match depth {
+			0 => split_into(part.split_inclusive('\n'), 1, max_length, pieces, piece),
+			1 => split_into(part.split_inclusive(' '), 2, max_length, pieces, piece),
+			_ => for character in part.chars() {
+				if piece.chars().count() >= max_length {
+					push_piece(pieces, std::mem::take(piece));
+				}
+				piece.push(character);
+			}
+		}}
+	}
+}
+
+fn push_piece(pieces: &mut Vec<String>, mut piece: String) {
+	piece.truncate(piece.trim_end().len());
+	if !piece.is_empty() {pieces.push(piece)}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{escape_markdown, escape_mentions, split_message, strip_markdown};
+
+	#[test]
+	fn code_block_test() {
+		assert_eq!(escape_markdown("```rust\nlet x = 1;\n```"),
+			"\\`\\`\\`rust\nlet x = 1;\n\\`\\`\\`");
+		assert_eq!(escape_markdown("`inline`"), "\\`inline\\`");
+	}
+
+	#[test]
+	fn nested_formatting_test() {
+		assert_eq!(escape_markdown("**_bold italic_**"),
+			"\\*\\*\\_bold italic\\_\\*\\*");
+		assert_eq!(escape_markdown("~~||spoiler||~~"),
+			"\\~\\~\\|\\|spoiler\\|\\|\\~\\~");
+		assert_eq!(escape_markdown("> quote \\*"), "\\> quote \\\\\\*");
+	}
+
+	#[test]
+	fn plain_text_test() {
+		assert_eq!(escape_markdown("nothing to see here"), "nothing to see here");
+		assert_eq!(escape_mentions("no pings"), "no pings");
+	}
+
+	#[test]
+	fn mentions_test() {
+		assert_eq!(escape_mentions("@everyone"), "@\u{200B}everyone");
+		assert_eq!(escape_mentions("@here and @room"), "@\u{200B}here and @\u{200B}room");
+		assert_eq!(escape_mentions("<@175036227875828737>"),
+			"<@\u{200B}175036227875828737>");
+		assert_eq!(escape_mentions("me@example.com"), "me@\u{200B}example.com");
+	}
+
+	#[test]
+	fn strip_formatting_test() {
+		assert_eq!(strip_markdown("**bold** and *italic* and __under__"),
+			"bold and italic and under");
+		assert_eq!(strip_markdown("~~gone~~ ||secret||"), "gone secret");
+		assert_eq!(strip_markdown("> quoted\nnot > quoted"), "quoted\nnot > quoted");
+		assert_eq!(strip_markdown(r"\*not bold\*"), "*not bold*");
+		assert_eq!(strip_markdown("snake_case_name and 2*3"), "snake_case_name and 2*3");
+		assert_eq!(strip_markdown("~ and | alone"), "~ and | alone");
+	}
+
+	#[test]
+	fn strip_code_test() {
+		assert_eq!(strip_markdown("run `**cargo**`"), "run **cargo**");
+		assert_eq!(strip_markdown("```rust\nlet _x = 1;\n```\ndone"), "let _x = 1;\ndone");
+		assert_eq!(strip_markdown("```one line```"), "one line");
+		assert_eq!(strip_markdown("unclosed `tick"), "unclosed `tick");
+	}
+
+	#[test]
+	fn strip_links_test() {
+		assert_eq!(strip_markdown("see [**the docs**](https://docs.rs)"),
+			"see the docs (https://docs.rs)");
+		assert_eq!(strip_markdown("[not a link] (here)"), "[not a link] (here)");
+		assert_eq!(strip_markdown("[broken](no end"), "[broken](no end");
+	}
+
+	#[test]
+	fn split_short_test() {
+		assert_eq!(split_message("hello", 10), vec!["hello"]);
+		assert!(split_message("", 10).is_empty());
+	}
+
+	#[test]
+	fn split_lines_test() {
+		assert_eq!(split_message("first line\nsecond line\nthird", 24),
+			vec!["first line\nsecond line", "third"]);
+	}
+
+	#[test]
+	fn split_words_test() {
+		assert_eq!(split_message("a very long line of words", 10),
+			vec!["a very", "long line", "of words"]);
+		assert_eq!(split_message("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+	}
+
+	#[test]
+	fn split_code_block_test() {
+		let text = "intro\n```\nlet a = 1;\nlet b = 2;\n```\noutro";
+		assert_eq!(split_message(text, 30),
+			vec!["intro", "```\nlet a = 1;\nlet b = 2;\n```", "outro"]);
+	}
+
+	#[test]
+	fn split_characters_test() {
+		assert_eq!(split_message("ééééé", 2), vec!["éé", "éé", "é"]);
+	}
+}
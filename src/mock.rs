@@ -0,0 +1,1039 @@
+//! An in-process stand in for hiven's gateway, for testing bots without the
+//! real service.
+//!
+//! The mock speaks just enough of the protocol for a [`GateKeeper`] to connect:
+//! it sends `Hello`, waits for `Login`, replies with a canned `INIT_STATE`, and
+//! then sends whatever events the test scripts. Only available with the
+//! `mock-gateway` feature.
+//!
+//! ```rust
+//! use hiven_rs::{EventHandler, GatewayExit, mock::MockGateway};
+//! use serde_json::json;
+//!
+//! struct Handler;
+//! impl EventHandler for Handler {}
+//!
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! let mock = MockGateway::bind().await.unwrap();
+//! let client = mock.client("token");
+//!
+//! let script = async {
+//!     let mut connection = mock.accept().await.unwrap();
+//!     connection.send_event("TYPING_START", json!({
+//!         "room_id": "1", "author_id": "2"
+//!     })).await.unwrap();
+//!     connection.close().await.unwrap();
+//! };
+//!
+//! let (result, ()) = tokio::join!(client.start_gateway(Handler), script);
+//! // The mock closed the connection.
+//! assert!(matches!(result, GatewayExit::Fatal(_)));
+//! # });
+//! ```
+//!
+//! [`GateKeeper`]: crate::client::GateKeeper
+
+use self::super::{
+	client::{Client, ClientBuilder},
+	gateway::Frame
+};
+use async_tungstenite::{
+	WebSocketStream,
+	tokio::{TokioAdapter, accept_async},
+	tungstenite::{
+		Error as WebsocketError,
+		Message as WebsocketMessage,
+		protocol::frame::{CloseFrame, coding::CloseCode}
+	}
+};
+use futures::{sink::SinkExt, stream::StreamExt};
+use serde_json::{Value as JSONValue, from_str as from_json, json};
+use tokio::{net::{TcpListener, TcpStream}, sync::Mutex};
+
+type Result<T> = std::result::Result<T, WebsocketError>;
+
+/// The `INIT_STATE` data sent to every client after it logs in.
+pub fn init_state() -> JSONValue {
+	json!({
+		"user": {
+			"username": "mock_user",
+			"name": "Mock User",
+			"id": "1",
+			"icon": null,
+			"header": null
+		},
+		"settings": {
+			"theme": null,
+			"enable_desktop_notifications": null
+		},
+		"private_rooms": [],
+		"house_ids": []
+	})
+}
+
+/// A gateway server listening on a local port.
+pub struct MockGateway {
+	// Behind a mutex so connections can be accepted through a shared reference.
+	listener: Mutex<TcpListener>,
+	host: String,
+	heart_beat: u16
+}
+
+impl MockGateway {
+	/// Starts listening on a random local port.
+	pub async fn bind() -> Result<Self> {
+		let listener = TcpListener::bind("127.0.0.1:0").await?;
+		let host = listener.local_addr()?.to_string();
+
+		Ok(Self {
+			listener: Mutex::new(listener),
+			host,
+			heart_beat: 30000
+		})
+	}
+
+	/// Sets the heart beat interval, in milliseconds, sent in `Hello`.
+	pub fn heart_beat(mut self, heart_beat: u16) -> Self {
+		self.heart_beat = heart_beat;
+		self
+	}
+
+	/// The address the mock is listening on, as a gateway domain.
+	pub fn host(&self) -> &str {
+		&self.host
+	}
+
+	/// Creates a builder for a client that connects to this mock.
+	pub fn client_builder<T>(&self, token: T) -> ClientBuilder
+			where T: Into<String> {
+		ClientBuilder::new(token).domains("127.0.0.1", self.host.as_str())
+			.insecure_gateway()
+	}
+
+	/// Creates a client that connects to this mock.
+	pub fn client<T>(&self, token: T) -> Client
+			where T: Into<String> {
+		self.client_builder(token).try_build().expect("the mock's host is valid")
+	}
+
+	/// Waits for a client to connect and takes it through the handshake, up to
+	/// and including sending `INIT_STATE`.
+	pub async fn accept(&self) -> Result<MockConnection> {
+		let (stream, _) = self.listener.lock().await.accept().await?;
+		let socket = accept_async(stream).await?;
+		let mut connection = MockConnection {
+			socket,
+			token: String::new()
+		};
+
+		connection.send(json!({"op": 1, "d": {"hbt_int": self.heart_beat}}))
+			.await?;
+		connection.token = loop {
+			match connection.next_frame().await? {
+				Some(Frame::Login(login)) => break login.token,
+				Some(_) => continue,
+				None => return Err(WebsocketError::ConnectionClosed)
+			}
+		};
+		connection.send_event("INIT_STATE", init_state()).await?;
+
+		Ok(connection)
+	}
+}
+
+/// A client connected to a [`MockGateway`].
+pub struct MockConnection {
+	socket: WebSocketStream<TokioAdapter<TcpStream>>,
+	/// The token the client logged in with.
+	pub token: String
+}
+
+impl MockConnection {
+	/// Sends an event frame to the client.
+	pub async fn send_event(&mut self, event: &str, data: JSONValue) ->
+			Result<()> {
+		self.send(json!({"op": 0, "e": event, "d": data})).await
+	}
+
+	/// Sends any JSON frame to the client.
+	pub async fn send(&mut self, frame: JSONValue) -> Result<()> {
+		self.send_raw(frame.to_string()).await
+	}
+
+	/// Sends a text frame to the client as is.
+	pub async fn send_raw(&mut self, frame: String) -> Result<()> {
+		self.socket.send(WebsocketMessage::Text(frame)).await
+	}
+
+	/// Sends a binary frame to the client as is.
+	pub async fn send_binary(&mut self, frame: Vec<u8>) -> Result<()> {
+		self.socket.send(WebsocketMessage::Binary(frame)).await
+	}
+
+	/// Waits for the next frame the client sends. Returns `None` once the
+	/// client disconnects. Frames that aren't valid are skipped.
+	pub async fn next_frame(&mut self) -> Result<Option<Frame>> {
+		while let Some(message) = self.socket.next().await {
+			match message? {
+				WebsocketMessage::Text(frame) => if let Ok(frame) = from_json(&frame) {
+					return Ok(Some(frame))
+				},
+				WebsocketMessage::Close(_) => return Ok(None),
+				_ => ()
+			}
+		}
+
+		Ok(None)
+	}
+
+	/// Closes the connection to the client.
+	pub async fn close(mut self) -> Result<()> {
+		self.socket.close(None).await
+	}
+
+	/// Closes the connection to the client with a close code, such as
+	/// [`AUTH_FAILED_CLOSE_CODE`](crate::gateway::AUTH_FAILED_CLOSE_CODE).
+	pub async fn close_with(mut self, code: u16, reason: &str) -> Result<()> {
+		self.socket.close(Some(CloseFrame {
+			code: CloseCode::from(code),
+			reason: reason.to_owned().into()
+		})).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{MockConnection, MockGateway};
+	use crate::{
+		Client, EventHandler, GateKeeper, GatewayExit,
+		client::{Error, TokenType},
+		data::Message,
+		gateway::{AUTH_FAILED_CLOSE_CODE, EventInitState, Frame, OpCodeEvent}
+	};
+	use async_tungstenite::{
+		tokio::accept_async,
+		tungstenite::{
+			Message as WebsocketMessage,
+			protocol::frame::{CloseFrame, coding::CloseCode}
+		}
+	};
+	use futures::{sink::SinkExt, stream::StreamExt};
+	use serde_json::json;
+	use std::{
+		future::Future, iter::{once, repeat_n}, pin::Pin,
+		sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}},
+		time::Duration
+	};
+	use tokio::{join, select, time::delay_for};
+
+	#[derive(Default)]
+	struct Recorder(Arc<Mutex<Vec<String>>>);
+
+	impl EventHandler for Recorder {
+		fn on_connect<'c>(&self, _client: &'c Client, event: EventInitState) ->
+				Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+			self.0.lock().unwrap().push(format!("connect {}", event.user.username));
+			Box::pin(async {})
+		}
+
+		fn on_message<'c>(&self, _client: &'c Client, event: Message) ->
+				Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+			self.0.lock().unwrap().push(format!("message {}", event.content));
+			Box::pin(async {})
+		}
+	}
+
+	#[tokio::test]
+	async fn dispatch_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client("token");
+		let recorder = Recorder::default();
+		let events = recorder.0.clone();
+
+		let script = async {
+			let mut connection = mock.accept().await.unwrap();
+			assert_eq!(connection.token, "token");
+			while !client.is_connected() {tokio::task::yield_now().await}
+
+			connection.send_event("MESSAGE_CREATE", json!({
+				"id": "3", "room_id": "2", "author_id": "1", "content": "hi"
+			})).await.unwrap();
+			connection.close().await.unwrap();
+		};
+
+		assert!(!client.is_connected());
+		let (result, ()) = join!(client.start_gateway(recorder), script);
+		assert!(matches!(result, GatewayExit::Fatal(_)));
+		assert!(!client.is_connected());
+		assert_eq!(*events.lock().unwrap(), vec!["connect mock_user", "message hi"]);
+		assert_eq!(client.current_user_id(), Some(1.into()));
+	}
+
+	#[derive(Default)]
+	struct DisconnectRecorder(Arc<Mutex<Vec<String>>>);
+
+	impl EventHandler for DisconnectRecorder {
+		fn on_disconnect<'c>(&self, _client: &'c Client,
+				close_frame: Option<CloseFrame<'static>>) ->
+					Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+			self.0.lock().unwrap().push(match close_frame {
+				Some(frame) => format!("{} {}", u16::from(frame.code), frame.reason),
+				None => "no close frame".to_owned()
+			});
+			Box::pin(async {})
+		}
+	}
+
+	#[derive(Default)]
+	struct RawRecorder(Arc<Mutex<Vec<String>>>);
+
+	impl EventHandler for RawRecorder {
+		fn on_raw(&self, _client: &Client, frame: &str) {
+			self.0.lock().unwrap().push(frame.to_owned());
+		}
+	}
+
+	#[derive(Default)]
+	struct ErrorRecorder(Arc<Mutex<Vec<String>>>);
+
+	impl EventHandler for ErrorRecorder {
+		fn on_message<'c>(&self, _client: &'c Client, event: Message) ->
+				Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+			self.0.lock().unwrap().push(format!("message {}", event.content));
+			Box::pin(async {})
+		}
+
+		fn on_error(&self, _client: &Client, error: &Error) {
+			if let Error::ExpectationFailed(expected, _) = error {
+				self.0.lock().unwrap().push(format!("expected {}", expected));
+			}
+		}
+	}
+
+	#[tokio::test]
+	async fn unexpected_frame_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client("token");
+		let recorder = ErrorRecorder::default();
+		let recorded = recorder.0.clone();
+
+		let script = async {
+			let mut connection = mock.accept().await.unwrap();
+			// A frame only clients send is reported, without ending the gateway.
+			connection.send(json!({"op": 2, "d": {"token": "token"}})).await.unwrap();
+			connection.send_event("MESSAGE_CREATE", json!({
+				"id": "3", "room_id": "2", "author_id": "1", "content": "still here"
+			})).await.unwrap();
+			connection.close().await.unwrap();
+		};
+
+		let (result, ()) = join!(client.start_gateway(recorder), script);
+		assert!(matches!(result, GatewayExit::Fatal(_)));
+		// Events and other frames take different paths, so either can come first.
+		let mut recorded = recorded.lock().unwrap().clone();
+		recorded.sort();
+		assert_eq!(recorded, vec!["expected a frame hiven sends", "message still here"]);
+	}
+
+	#[tokio::test]
+	async fn raw_frame_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client("token");
+		let recorder = RawRecorder::default();
+		let frames = recorder.0.clone();
+
+		let script = async {
+			let mut connection = mock.accept().await.unwrap();
+			connection.send_raw("not a frame".to_owned()).await.unwrap();
+			connection.send_raw(r#"{"op":0,"e":"SOMETHING_NEW","d":{}}"#.to_owned())
+				.await.unwrap();
+			connection.close().await.unwrap();
+		};
+
+		let (result, ()) = join!(client.start_gateway(recorder), script);
+		assert!(matches!(result, GatewayExit::Fatal(_)));
+		// Hello and the initial state come first, then the frames as sent.
+		let frames = frames.lock().unwrap();
+		assert_eq!(frames.len(), 4);
+		assert!(frames[1].contains("INIT_STATE"));
+		assert_eq!(frames[2..], ["not a frame", r#"{"op":0,"e":"SOMETHING_NEW","d":{}}"#]);
+	}
+
+	#[tokio::test]
+	async fn disconnect_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client("token");
+		let recorder = DisconnectRecorder::default();
+		let events = recorder.0.clone();
+
+		let script = async {
+			let mut connection = mock.accept().await.unwrap();
+			connection.socket.close(Some(CloseFrame {
+				code: CloseCode::from(4001),
+				reason: "Session expired".into()
+			})).await.unwrap();
+		};
+
+		let (result, ()) = join!(client.start_gateway(recorder), script);
+		match result {
+			GatewayExit::Fatal(Error::SocketClose(Some(frame))) =>
+				assert_eq!(frame.code, CloseCode::Library(4001)),
+			exit => panic!("expected SocketClose, got {:?}", exit)
+		}
+		assert_eq!(*events.lock().unwrap(), vec!["4001 Session expired"]);
+	}
+
+	#[tokio::test]
+	async fn heart_beat_test() {
+		let mock = MockGateway::bind().await.unwrap().heart_beat(10);
+		let client = mock.client("token");
+
+		let script = async {
+			let mut connection = mock.accept().await.unwrap();
+			match connection.next_frame().await.unwrap() {
+				Some(Frame::HeartBeat) => (),
+				frame => panic!("expected HeartBeat, got {:?}", frame)
+			}
+			connection.close().await.unwrap();
+		};
+
+		let (result, ()) = join!(client.start_gateway(Recorder::default()), script);
+		assert!(matches!(result, GatewayExit::Fatal(_)));
+	}
+
+	#[tokio::test]
+	async fn heart_beat_request_test() {
+		// Long enough that a timed heart beat can't get in first.
+		let mock = MockGateway::bind().await.unwrap().heart_beat(60000);
+		let client = mock.client("token");
+
+		let script = async {
+			let mut connection = mock.accept().await.unwrap();
+			connection.send(json!({"op": 3})).await.unwrap();
+			match connection.next_frame().await.unwrap() {
+				Some(Frame::HeartBeat) => (),
+				frame => panic!("expected HeartBeat, got {:?}", frame)
+			}
+			connection.close().await.unwrap();
+		};
+
+		let (result, ()) = join!(client.start_gateway(Recorder::default()), script);
+		assert!(matches!(result, GatewayExit::Fatal(_)));
+	}
+
+	#[tokio::test]
+	async fn heart_beat_change_test() {
+		// Long enough that only the changed interval can send a heart beat.
+		let mock = MockGateway::bind().await.unwrap().heart_beat(60000);
+		let client = mock.client("token");
+
+		let script = async {
+			let mut connection = mock.accept().await.unwrap();
+			connection.send(json!({"op": 1, "d": {"hbt_int": 10}})).await.unwrap();
+			match connection.next_frame().await.unwrap() {
+				Some(Frame::HeartBeat) => (),
+				frame => panic!("expected HeartBeat, got {:?}", frame)
+			}
+			connection.close().await.unwrap();
+		};
+
+		let (result, ()) = join!(client.start_gateway(Recorder::default()), script);
+		assert!(matches!(result, GatewayExit::Fatal(_)));
+	}
+
+	#[tokio::test]
+	async fn send_frame_test() {
+		let mock = MockGateway::bind().await.unwrap().heart_beat(60000);
+		let client = mock.client("token");
+		// Queued before connecting, so it has to wait for the login.
+		client.send_frame(Frame::HeartBeat);
+
+		let script = async {
+			let mut connection = mock.accept().await.unwrap();
+			match connection.next_frame().await.unwrap() {
+				Some(Frame::HeartBeat) => (),
+				frame => panic!("expected HeartBeat, got {:?}", frame)
+			}
+			connection.close().await.unwrap();
+		};
+
+		let (result, ()) = join!(client.start_gateway(Recorder::default()), script);
+		assert!(matches!(result, GatewayExit::Fatal(_)));
+	}
+
+	#[tokio::test]
+	async fn close_on_drop_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client("token");
+
+		// Dropping the gateway as soon as the mock has sent the initial state.
+		let mut connection = select! {
+			_ = client.start_gateway(Recorder::default()) => unreachable!(),
+			connection = mock.accept() => connection.unwrap()
+		};
+
+		loop {
+			match connection.socket.next().await.unwrap().unwrap() {
+				WebsocketMessage::Close(_) => break,
+				WebsocketMessage::Text(_) => continue,
+				message => panic!("expected Close, got {:?}", message)
+			}
+		}
+	}
+
+	#[tokio::test]
+	async fn handshake_timeout_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client_builder("token")
+			.handshake_timeout(Duration::from_millis(50)).try_build().unwrap();
+
+		// Accept the websocket, but never say hello.
+		let script = async {
+			let (stream, _) = mock.listener.lock().await.accept().await.unwrap();
+			let mut socket = accept_async(stream).await.unwrap();
+			while socket.next().await.is_some() {}
+		};
+
+		let (result, ()) = join!(client.start_gateway(Recorder::default()), script);
+		match result {
+			GatewayExit::Fatal(Error::HandshakeTimeout) => (),
+			result => panic!("expected HandshakeTimeout, got {:?}", result)
+		}
+	}
+
+	#[tokio::test]
+	async fn handshake_timeout_after_hello_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client_builder("token")
+			.handshake_timeout(Duration::from_millis(50)).try_build().unwrap();
+
+		// Say hello and take the login, but never send the initial state.
+		let script = async {
+			let mut connection = accept_without_init(&mock).await;
+			while let Ok(Some(_)) = connection.next_frame().await {}
+		};
+
+		let (result, ()) = join!(client.start_gateway(Recorder::default()), script);
+		match result {
+			GatewayExit::Fatal(Error::HandshakeTimeout) => (),
+			result => panic!("expected HandshakeTimeout, got {:?}", result)
+		}
+	}
+
+	#[tokio::test]
+	async fn connect_timeout_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client_builder("token")
+			.connect_timeout(Duration::from_millis(50)).try_build().unwrap();
+
+		// The connection is never accepted, so the websocket handshake hangs.
+		match client.start_gateway(Recorder::default()).await {
+			GatewayExit::Fatal(Error::ConnectTimeout) => (),
+			result => panic!("expected ConnectTimeout, got {:?}", result)
+		}
+	}
+
+	// Says hello and waits for the login, without sending the initial state.
+	async fn accept_without_init(mock: &MockGateway) -> MockConnection {
+		let (stream, _) = mock.listener.lock().await.accept().await.unwrap();
+		let socket = accept_async(stream).await.unwrap();
+		let mut connection = MockConnection {socket, token: String::new()};
+		connection.send(json!({"op": 1, "d": {"hbt_int": 30000}})).await.unwrap();
+		while let Some(frame) = connection.next_frame().await.unwrap() {
+			if let Frame::Login(_) = frame {break}
+		}
+		connection
+	}
+
+	#[tokio::test]
+	async fn auth_failed_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		// Even when reconnecting, a rejected token isn't retried.
+		let client = mock.client_builder("bad").reconnect(true).try_build().unwrap();
+
+		let script = async {
+			accept_without_init(&mock).await
+				.close_with(AUTH_FAILED_CLOSE_CODE, "invalid token").await.unwrap();
+		};
+
+		let (result, ()) = join!(client.start_gateway(Recorder::default()), script);
+		assert!(matches!(result, GatewayExit::AuthFailed));
+		assert_eq!(client.connection_attempts(), 1);
+	}
+
+	#[tokio::test]
+	async fn dropped_before_login_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client_builder("token").reconnect(true).try_build().unwrap();
+		let gate_keeper = GateKeeper::new(&client, Recorder::default());
+		let shutdown = gate_keeper.shutdown_handle();
+
+		// A connection lost before the initial state isn't a rejected token.
+		let script = async {
+			accept_without_init(&mock).await.close().await.unwrap();
+			let mut connection = mock.accept().await.unwrap();
+			while !client.is_connected() {tokio::task::yield_now().await}
+			shutdown.shutdown();
+			while connection.next_frame().await.unwrap().is_some() {}
+		};
+
+		let (result, ()) = join!(gate_keeper.start_gateway(), script);
+		assert!(matches!(result, GatewayExit::Shutdown));
+		assert_eq!(client.connection_attempts(), 2);
+
+		// Without reconnecting, it just ends the gateway.
+		let client = mock.client("token");
+		let script = async {accept_without_init(&mock).await.close().await.unwrap()};
+		let (result, ()) = join!(client.start_gateway(Recorder::default()), script);
+		assert!(matches!(result, GatewayExit::Fatal(Error::SocketClose(None))));
+	}
+
+	#[tokio::test]
+	async fn keepalive_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client_builder("token")
+			.keepalive(Some(Duration::from_millis(10))).try_build().unwrap();
+
+		let script = async {
+			let mut connection = mock.accept().await.unwrap();
+			loop {
+				match connection.socket.next().await.unwrap().unwrap() {
+					WebsocketMessage::Ping(_) => break,
+					WebsocketMessage::Text(_) => continue,
+					message => panic!("expected Ping, got {:?}", message)
+				}
+			}
+			connection.close().await.unwrap();
+		};
+
+		let (result, ()) = join!(client.start_gateway(Recorder::default()), script);
+		assert!(matches!(result, GatewayExit::Fatal(_)));
+	}
+
+	#[tokio::test]
+	async fn ping_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client("token");
+
+		let script = async {
+			let mut connection = mock.accept().await.unwrap();
+			connection.socket.send(WebsocketMessage::Ping(vec![1, 2])).await
+				.unwrap();
+			loop {
+				match connection.socket.next().await.unwrap().unwrap() {
+					WebsocketMessage::Pong(data) => break assert_eq!(data, vec![1, 2]),
+					WebsocketMessage::Text(_) => continue,
+					message => panic!("expected Pong, got {:?}", message)
+				}
+			}
+			connection.close().await.unwrap();
+		};
+
+		let (result, ()) = join!(client.start_gateway(Recorder::default()), script);
+		assert!(matches!(result, GatewayExit::Fatal(_)));
+	}
+
+	struct SlowRecorder(Arc<Mutex<Vec<String>>>);
+
+	impl EventHandler for SlowRecorder {
+		fn on_message<'c>(&self, _client: &'c Client, event: Message) ->
+				Pin<Box<dyn Future<Output = ()> + Send + 'c>> {
+			let events = self.0.clone();
+			Box::pin(async move {
+				if event.content == "slow" {delay_for(Duration::from_millis(100)).await}
+				events.lock().unwrap().push(event.content);
+			})
+		}
+	}
+
+	#[tokio::test]
+	async fn event_concurrency_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client_builder("token").event_concurrency(2).try_build().unwrap();
+		let events = Arc::new(Mutex::new(Vec::new()));
+
+		let script = async {
+			let mut connection = mock.accept().await.unwrap();
+			for content in ["slow", "fast"].iter() {
+				connection.send_event("MESSAGE_CREATE", json!({
+					"id": "3", "room_id": "2", "author_id": "1", "content": content
+				})).await.unwrap();
+			}
+			delay_for(Duration::from_millis(50)).await;
+			connection.close().await.unwrap();
+		};
+
+		let (result, ()) = join!(client.start_gateway(SlowRecorder(events.clone())),
+			script);
+		assert!(matches!(result, GatewayExit::Fatal(_)));
+		assert_eq!(*events.lock().unwrap(), vec!["fast", "slow"]);
+	}
+
+	#[tokio::test]
+	async fn slow_handler_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client("token");
+		let events = Arc::new(Mutex::new(Vec::new()));
+
+		// Far more events than fit in any buffer, all behind a slow one.
+		let script = async {
+			let mut connection = mock.accept().await.unwrap();
+			for content in once("slow").chain(repeat_n("fast", 300)) {
+				connection.send_event("MESSAGE_CREATE", json!({
+					"id": "3", "room_id": "2", "author_id": "1", "content": content
+				})).await.unwrap();
+			}
+			connection.close().await.unwrap();
+		};
+
+		let (result, ()) = join!(client.start_gateway(SlowRecorder(events.clone())),
+			script);
+		assert!(matches!(result, GatewayExit::Fatal(_)));
+		let events = events.lock().unwrap();
+		assert_eq!((events.len(), events[0].as_str()), (301, "slow"));
+	}
+
+	#[tokio::test]
+	async fn two_gateways_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client("token");
+		let (first, second) = (Recorder::default(), Recorder::default());
+		let recorded = (first.0.clone(), second.0.clone());
+
+		let script = async {
+			for content in ["one", "two"].iter() {
+				let mut connection = mock.accept().await.unwrap();
+				connection.send_event("MESSAGE_CREATE", json!({
+					"id": "3", "room_id": "2", "author_id": "1", "content": content
+				})).await.unwrap();
+				connection.close().await.unwrap();
+			}
+		};
+
+		join!(client.start_gateway(first), client.start_gateway(second), script);
+		// Each handler only sees the events of its own connection.
+		let mut messages = Vec::new();
+		for recorded in [recorded.0, recorded.1].iter() {
+			let recorded = recorded.lock().unwrap();
+			assert_eq!(recorded.len(), 2);
+			messages.push(recorded[1].clone());
+		}
+		messages.sort();
+		assert_eq!(messages, vec!["message one", "message two"]);
+	}
+
+	#[tokio::test]
+	async fn connect_and_wait_ready_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client("token");
+
+		let script = async {
+			let mut connection = mock.accept().await.unwrap();
+			// The client closing the connection ends it.
+			while connection.next_frame().await.unwrap().is_some() {}
+		};
+
+		let ready = async {
+			let (init_state, handle) = client.connect_and_wait_ready().await.unwrap();
+			assert_eq!(init_state.user.username, "mock_user");
+			assert!(client.is_connected());
+			handle.shutdown().await
+		};
+
+		let (result, ()) = join!(ready, script);
+		assert!(matches!(result, GatewayExit::Shutdown));
+		assert!(!client.is_connected());
+	}
+
+	#[tokio::test]
+	async fn await_message_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client("token");
+
+		let script = async {
+			let mut connection = mock.accept().await.unwrap();
+			let messages = async {
+				for content in ["no", "yes"].iter() {
+					connection.send_event("MESSAGE_CREATE", json!({
+						"id": "3", "room_id": "2", "author_id": "1", "content": content
+					})).await.unwrap();
+				}
+			};
+
+			let (message, ()) = join!(client.await_message(
+				|message| message.content == "yes", Duration::from_secs(5)), messages);
+			assert_eq!(message.unwrap().content, "yes");
+			connection.close().await.unwrap();
+		};
+
+		let (result, ()) = join!(client.start_gateway(Recorder::default()), script);
+		assert!(matches!(result, GatewayExit::Fatal(_)));
+		assert!(client.await_message(|_| true, Duration::from_millis(10)).await.is_none());
+	}
+
+	#[tokio::test]
+	async fn events_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client("token");
+		let recorder = Recorder::default();
+		let recorded = recorder.0.clone();
+		let mut events = Box::pin(client.events());
+
+		let script = async {
+			let mut connection = mock.accept().await.unwrap();
+			connection.send_event("MESSAGE_CREATE", json!({
+				"id": "3", "room_id": "2", "author_id": "1", "content": "hi"
+			})).await.unwrap();
+			connection.close().await.unwrap();
+		};
+
+		let (result, ()) = join!(client.start_gateway(recorder), script);
+		assert!(matches!(result, GatewayExit::Fatal(_)));
+		// Both the handler and the stream saw every event.
+		assert_eq!(*recorded.lock().unwrap(), vec!["connect mock_user", "message hi"]);
+		assert!(matches!(events.next().await, Some(Ok(OpCodeEvent::InitState(_)))));
+		match events.next().await {
+			Some(Ok(OpCodeEvent::MessageCreate(message))) => assert_eq!(message.content, "hi"),
+			event => panic!("expected MessageCreate, got {:?}", event)
+		}
+	}
+
+	#[tokio::test]
+	async fn binary_frame_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client("token");
+		let recorder = Recorder::default();
+		let recorded = recorder.0.clone();
+
+		let script = async {
+			let mut connection = mock.accept().await.unwrap();
+			let frame = json!({"op": 0, "e": "MESSAGE_CREATE", "d": {
+				"id": "3", "room_id": "2", "author_id": "1", "content": "binary"
+			}});
+			// Not UTF-8, so it's skipped without dropping the connection.
+			connection.send_binary(vec![0x78, 0x9c, 0xff]).await.unwrap();
+			connection.send_binary(frame.to_string().into_bytes()).await.unwrap();
+			connection.close().await.unwrap();
+		};
+
+		let (result, ()) = join!(client.start_gateway(recorder), script);
+		assert!(matches!(result, GatewayExit::Fatal(_)));
+		assert_eq!(*recorded.lock().unwrap(), vec!["connect mock_user", "message binary"]);
+	}
+
+	#[tokio::test]
+	async fn multiple_handlers_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client("token");
+		let (first, second) = (Recorder::default(), Recorder::default());
+		let (first_events, second_events) = (first.0.clone(), second.0.clone());
+
+		let script = async {
+			let mut connection = mock.accept().await.unwrap();
+			connection.send_event("MESSAGE_CREATE", json!({
+				"id": "3", "room_id": "2", "author_id": "1", "content": "hi"
+			})).await.unwrap();
+			connection.close().await.unwrap();
+		};
+
+		let (result, ()) = join!(client.start_gateway(vec![first, second]), script);
+		assert!(matches!(result, GatewayExit::Fatal(_)));
+		assert_eq!(*first_events.lock().unwrap(), vec!["connect mock_user", "message hi"]);
+		assert_eq!(*second_events.lock().unwrap(), vec!["connect mock_user", "message hi"]);
+	}
+
+	#[tokio::test]
+	async fn reconnect_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client_builder("token").reconnect(true).try_build().unwrap();
+		let gate_keeper = GateKeeper::new(&client, Recorder::default());
+		let shutdown = gate_keeper.shutdown_handle();
+
+		let script = async {
+			let connection = mock.accept().await.unwrap();
+			while !client.is_connected() {tokio::task::yield_now().await}
+			assert!(client.uptime().is_some());
+			connection.close().await.unwrap();
+
+			let mut connection = mock.accept().await.unwrap();
+			while client.reconnects() < 1 {tokio::task::yield_now().await}
+			shutdown.shutdown();
+			while connection.next_frame().await.unwrap().is_some() {}
+		};
+
+		let (result, ()) = join!(gate_keeper.start_gateway(), script);
+		assert!(matches!(result, GatewayExit::Shutdown));
+		assert_eq!(client.connection_attempts(), 2);
+		assert_eq!(client.reconnects(), 1);
+		assert!(client.last_disconnect_reason().unwrap().starts_with("SocketClose"));
+		assert!(client.uptime().is_none());
+	}
+
+	#[tokio::test]
+	async fn shutdown_and_wait_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client("token");
+		let gate_keeper = GateKeeper::new(&client, Recorder::default());
+		let shutdown = gate_keeper.shutdown_handle();
+		let stopped = AtomicBool::new(false);
+
+		let gateway = async {
+			let exit = gate_keeper.start_gateway().await;
+			stopped.store(true, Ordering::Release);
+			exit
+		};
+		let script = async {
+			let mut connection = mock.accept().await.unwrap();
+			while !client.is_connected() {tokio::task::yield_now().await}
+			join!(shutdown.shutdown_and_wait(), async {
+				while connection.next_frame().await.unwrap().is_some() {}
+			});
+			// Only resolves once the gateway is done, not just told to stop.
+			assert!(stopped.load(Ordering::Acquire));
+			assert!(!client.is_connected());
+		};
+
+		let (result, ()) = join!(gateway, script);
+		assert!(matches!(result, GatewayExit::Shutdown));
+		// With the gateway stopped, there's nothing to wait for.
+		shutdown.shutdown_and_wait().await;
+	}
+
+	#[tokio::test]
+	async fn max_reconnect_attempts_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client_builder("token").reconnect(true)
+			.max_reconnect_attempts(Some(2)).try_build().unwrap();
+		// Nothing's listening anymore, so every attempt fails.
+		drop(mock);
+
+		match client.start_gateway(Recorder::default()).await {
+			GatewayExit::Fatal(Error::ReconnectExhausted(_)) => (),
+			exit => panic!("expected ReconnectExhausted, got {:?}", exit)
+		}
+		assert_eq!(client.connection_attempts(), 3);
+	}
+
+	#[tokio::test]
+	async fn unlimited_reconnect_attempts_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client_builder("token").reconnect(true).try_build().unwrap();
+		let gate_keeper = GateKeeper::new(&client, Recorder::default());
+		let shutdown = gate_keeper.shutdown_handle();
+		drop(mock);
+
+		let script = async {
+			while client.connection_attempts() < 3 {delay_for(Duration::from_millis(10)).await}
+			shutdown.shutdown();
+		};
+
+		let (result, ()) = join!(gate_keeper.start_gateway(), script);
+		assert!(matches!(result, GatewayExit::Shutdown));
+	}
+
+	#[tokio::test]
+	async fn set_token_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client("old");
+		let gate_keeper = GateKeeper::new(&client, Recorder::default());
+		let shutdown = gate_keeper.shutdown_handle();
+
+		let script = async {
+			let mut connection = mock.accept().await.unwrap();
+			assert_eq!(connection.token, "old");
+			while !client.is_connected() {tokio::task::yield_now().await}
+			client.set_token("new".to_owned());
+			while connection.next_frame().await.unwrap().is_some() {}
+
+			let mut connection = mock.accept().await.unwrap();
+			assert_eq!(connection.token, "new");
+			shutdown.shutdown();
+			while connection.next_frame().await.unwrap().is_some() {}
+		};
+
+		let (result, ()) = join!(gate_keeper.start_gateway(), script);
+		assert!(matches!(result, GatewayExit::Shutdown));
+		assert!(client.last_disconnect_reason().is_none());
+	}
+
+	#[tokio::test]
+	async fn manual_reconnect_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let recorder = Recorder::default();
+		let recorded = recorder.0.clone();
+		let client = mock.client("token");
+		let gate_keeper = GateKeeper::new(&client, recorder);
+		let shutdown = gate_keeper.shutdown_handle();
+
+		let script = async {
+			let mut connection = mock.accept().await.unwrap();
+			while !client.is_connected() {tokio::task::yield_now().await}
+			client.reconnect();
+			while connection.next_frame().await.unwrap().is_some() {}
+
+			let mut connection = mock.accept().await.unwrap();
+			assert_eq!(connection.token, "token");
+			while client.reconnects() < 1 {tokio::task::yield_now().await}
+			shutdown.shutdown();
+			while connection.next_frame().await.unwrap().is_some() {}
+		};
+
+		// Reconnecting isn't turned on, but asking for it still works.
+		let (result, ()) = join!(gate_keeper.start_gateway(), script);
+		assert!(matches!(result, GatewayExit::Shutdown));
+		assert_eq!(*recorded.lock().unwrap(), vec!["connect mock_user", "connect mock_user"]);
+		assert!(client.last_disconnect_reason().is_none());
+	}
+
+	#[tokio::test]
+	async fn bearer_login_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client_builder("token").token_type(TokenType::Bearer)
+			.try_build().unwrap();
+		let gate_keeper = GateKeeper::new(&client, Recorder::default());
+		let shutdown = gate_keeper.shutdown_handle();
+
+		let script = async {
+			let mut connection = mock.accept().await.unwrap();
+			assert_eq!(connection.token, "Bearer token");
+			shutdown.shutdown();
+			while connection.next_frame().await.unwrap().is_some() {}
+		};
+
+		let (result, ()) = join!(gate_keeper.start_gateway(), script);
+		assert!(matches!(result, GatewayExit::Shutdown));
+	}
+
+	#[tokio::test]
+	async fn set_token_events_test() {
+		let mock = MockGateway::bind().await.unwrap();
+		let client = mock.client("old");
+		let recorder = Recorder::default();
+		let recorded = recorder.0.clone();
+		let gate_keeper = GateKeeper::new(&client, recorder);
+		let shutdown = gate_keeper.shutdown_handle();
+
+		let script = async {
+			let mut connection = mock.accept().await.unwrap();
+			while !client.is_connected() {tokio::task::yield_now().await}
+			client.set_token("new".to_owned());
+			while connection.next_frame().await.unwrap().is_some() {}
+
+			// The same handler keeps getting events on the new connection.
+			let mut connection = mock.accept().await.unwrap();
+			connection.send_event("MESSAGE_CREATE", json!({
+				"id": "3", "room_id": "2", "author_id": "1", "content": "still here"
+			})).await.unwrap();
+			while recorded.lock().unwrap().len() < 3 {tokio::task::yield_now().await}
+			shutdown.shutdown();
+			while connection.next_frame().await.unwrap().is_some() {}
+		};
+
+		let (result, ()) = join!(gate_keeper.start_gateway(), script);
+		assert!(matches!(result, GatewayExit::Shutdown));
+		assert_eq!(*recorded.lock().unwrap(),
+			vec!["connect mock_user", "connect mock_user", "message still here"]);
+		// Swapping tokens doesn't count as losing the connection.
+		assert!(client.last_disconnect_reason().is_none());
+	}
+}
@@ -1,26 +1,71 @@
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
 use serde::{
 	Deserialize,
 	de::{Deserializer, Error as DeserializeError, Unexpected}
 };
 
-const FROM_STR_ERR: &'static str =
+// Everything but unreserved characters, see RFC 3986, section 2.3.
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+	.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+const FROM_STR_ERR: &str =
 	"string value that can be parsed into other values";
 
+// Strings are deserialized owned, rather than borrowed, so these also work on
+// frames that had to be buffered before being deserialized.
 pub(crate) fn from_str<'d, T, D>(deserializer: D) -> Result<T, D::Error>
 		where T: std::str::FromStr,
 			D: Deserializer<'d> {
-	let string = <&'d str>::deserialize(deserializer)?;
+	let string = String::deserialize(deserializer)?;
 	T::from_str(&string).map_err(|_| DeserializeError::invalid_value(
-		Unexpected::Str(string), &FROM_STR_ERR))
+		Unexpected::Str(&string), &FROM_STR_ERR))
 }
 
 pub(crate) fn from_str_opt<'d, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
 		where T: std::str::FromStr,
 			D: Deserializer<'d> {
-	let string = Option::<&'d str>::deserialize(deserializer)?;
+	let string = Option::<String>::deserialize(deserializer)?;
 	Ok(match string {
 		Some(string) => Some(T::from_str(&string).map_err(|_| DeserializeError
-			::invalid_value(Unexpected::Str(string), &FROM_STR_ERR))?),
+			::invalid_value(Unexpected::Str(&string), &FROM_STR_ERR))?),
 		None => None
 	})
 }
+
+pub(crate) fn from_str_seq<'d, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+		where T: std::str::FromStr,
+			D: Deserializer<'d> {
+	Vec::<String>::deserialize(deserializer)?.into_iter()
+		.map(|string| T::from_str(&string).map_err(|_| DeserializeError
+			::invalid_value(Unexpected::Str(&string), &FROM_STR_ERR)))
+		.collect()
+}
+
+const BASE64: &[u8; 64] =
+	b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes an image as a `data:` uri, for endpoints that take uploads inline.
+/// Gifs are told apart from other images by their header, anything else is
+/// sent as a png.
+pub(crate) fn encode_image(image: &[u8]) -> String {
+	let mime = if image.starts_with(b"GIF8") {"image/gif"} else {"image/png"};
+	let mut uri = format!("data:{};base64,", mime);
+
+	for chunk in image.chunks(3) {
+		let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+		let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+		for index in 0..4 {
+			if index <= chunk.len() {
+				uri.push(BASE64[(bits >> (18 - index * 6)) as usize & 63] as char);
+			} else {uri.push('=')}
+		}
+	}
+
+	uri
+}
+
+/// Percent-encodes a value, such as an emoji, for use as one segment of a url
+/// path.
+pub(crate) fn encode_path_segment(segment: &str) -> String {
+	utf8_percent_encode(segment, PATH_SEGMENT).to_string()
+}
@@ -0,0 +1,140 @@
+//! Checks that payloads shaped like the ones hiven sends, kept under
+//! `tests/fixtures`, still deserialize into the types they're meant for. The
+//! gateway skips frames it can't parse, so drift would otherwise go unnoticed.
+
+use hiven_rs::{
+	data::{House, Message, Presence, UserFlags},
+	gateway::{Frame, OpCodeEvent},
+	http::ResponseInfo
+};
+use serde::de::DeserializeOwned;
+use serde_json::from_str as from_json;
+
+macro_rules! fixture {
+	($path:literal) => {include_str!(concat!("fixtures/", $path))}
+}
+
+fn event(frame: &str) -> OpCodeEvent {
+	match from_json::<Frame>(frame).unwrap() {
+		Frame::Event(event) => event,
+		frame => panic!("expected an event, got {:?}", frame)
+	}
+}
+
+fn response<T>(response: &str) -> T
+		where T: DeserializeOwned {
+	from_json::<ResponseInfo<T>>(response).unwrap().data
+}
+
+fn check_house(house: &House) {
+	assert_eq!(house.id, 175036227875828801);
+	assert_eq!(house.name, "Bot Testing");
+	assert_eq!(house.categories[0].name, "Rooms");
+	assert_eq!(house.rooms_in(175036227875828810).count(), 1);
+	assert_eq!(house.rooms[0].last_message_id, Some(175036227875828900.into()));
+	assert_eq!(u32::from(house.roles[0].color.unwrap()), 0xFF5555);
+	// Only members hiven sent a presence for count as online.
+	assert_eq!(house.member_count(), 2);
+	assert_eq!(house.online_count(), 1);
+	assert_eq!(house.members[0].presence, None);
+	assert_eq!(house.members[1].presence, Some(Presence::Online));
+	assert_eq!(house.is_online(house.members[0].user.id), None);
+	assert!(house.members[1].user.flags.contains(UserFlags::BOT));
+}
+
+#[test]
+fn message_response_test() {
+	let message: Message = response(fixture!("responses/message.json"));
+	assert_eq!(message.id, 175036227875828900);
+	assert_eq!(message.house_id, Some(175036227875828801.into()));
+	assert_eq!(message.content, "Hello!");
+	assert!(!message.is_system());
+}
+
+#[test]
+fn house_response_test() {
+	check_house(&response(fixture!("responses/house.json")));
+}
+
+#[test]
+fn init_state_test() {
+	match event(fixture!("events/init_state.json")) {
+		OpCodeEvent::InitState(event) => {
+			assert!(event.user.is_bot());
+			assert_eq!(event.house_ids, vec![175036227875828801]);
+			assert_eq!(event.private_rooms[0].recipients[0].username, "someone");
+		},
+		event => panic!("expected InitState, got {:?}", event)
+	}
+}
+
+#[test]
+fn house_join_test() {
+	match event(fixture!("events/house_join.json")) {
+		OpCodeEvent::HouseJoin(house) => check_house(&house),
+		event => panic!("expected HouseJoin, got {:?}", event)
+	}
+}
+
+#[test]
+fn house_leave_test() {
+	match event(fixture!("events/house_leave.json")) {
+		OpCodeEvent::HouseLeave(event) => assert_eq!(event.house_id, 175036227875828801),
+		event => panic!("expected HouseLeave, got {:?}", event)
+	}
+
+	match event(fixture!("events/house_delete.json")) {
+		OpCodeEvent::HouseDelete(event) => assert_eq!(event.house_id, 175036227875828801),
+		event => panic!("expected HouseDelete, got {:?}", event)
+	}
+}
+
+#[test]
+fn typing_start_test() {
+	match event(fixture!("events/typing_start.json")) {
+		OpCodeEvent::TypingStart(event) => {
+			assert_eq!(event.user_id, 175036227875828738);
+			assert_eq!(event.timestamp, Some(1602763200000));
+		},
+		event => panic!("expected TypingStart, got {:?}", event)
+	}
+}
+
+#[test]
+fn message_create_test() {
+	match event(fixture!("events/message_create.json")) {
+		OpCodeEvent::MessageCreate(message) => {
+			assert_eq!(message.author_id, 175036227875828738);
+			assert_eq!(message.mentions[0].id, 175036227875828737);
+		},
+		event => panic!("expected MessageCreate, got {:?}", event)
+	}
+}
+
+#[test]
+fn presence_update_test() {
+	match event(fixture!("events/presence_update.json")) {
+		OpCodeEvent::PresenceUpdate(event) => {
+			assert_eq!(event.user_id, 175036227875828737);
+			assert_eq!(event.presence, Presence::Offline);
+		},
+		event => panic!("expected PresenceUpdate, got {:?}", event)
+	}
+}
+
+#[test]
+fn message_pin_test() {
+	match event(fixture!("events/message_pin.json")) {
+		OpCodeEvent::MessagePinned(event) =>
+			assert_eq!(event.house_id, Some(175036227875828801.into())),
+		event => panic!("expected MessagePinned, got {:?}", event)
+	}
+
+	match event(fixture!("events/message_unpin.json")) {
+		OpCodeEvent::MessageUnpinned(event) => {
+			assert_eq!(event.message_id, 175036227875828900);
+			assert!(event.message.is_none());
+		},
+		event => panic!("expected MessageUnpinned, got {:?}", event)
+	}
+}